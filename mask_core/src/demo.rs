@@ -0,0 +1,118 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::platform::{GameKey, Input};
+
+/*
+ * A demo is the list of key events of a playthrough, each tagged with the frame (the
+ * number of Input::read_key calls before it) it happened in. Since the game is
+ * deterministic, replaying the events from level 0 reproduces the playthrough.
+ *
+ * Text format: one "<frame> <key>" event per line, key being one of left, right, up,
+ * down, space or tab. Empty lines and lines starting with # are ignored.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Demo {
+    pub events: Vec<(usize, GameKey)>,
+}
+
+impl Demo {
+    pub fn new() -> Self {
+        Demo {
+            events: vec![],
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = vec![];
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let frame = parts.next()
+                .and_then(|frame| frame.parse::<usize>().ok())
+                .ok_or_else(|| format!("line {}: expected a frame number", line_num + 1))?;
+            let key_name = parts.next().unwrap_or("");
+            let key = GameKey::from_name(key_name)
+                .ok_or_else(|| format!("line {}: unknown key '{}'", line_num + 1, key_name))?;
+            events.push((frame, key));
+        }
+        events.sort_by_key(|(frame, _)| *frame);
+
+        Ok(Demo { events })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# MaskOS demo: <frame> <key>\n");
+        for (frame, key) in self.events.iter() {
+            text += &format!("{} {}\n", frame, key.name());
+        }
+        text
+    }
+}
+
+/// Passes keys through from another input and records them into a demo.
+pub struct RecordingInput<I: Input> {
+    inner: I,
+    demo: Demo,
+    frame: usize,
+}
+
+impl<I: Input> RecordingInput<I> {
+    pub fn new(inner: I) -> Self {
+        RecordingInput {
+            inner,
+            demo: Demo::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn demo(&self) -> &Demo {
+        &self.demo
+    }
+}
+
+impl<I: Input> Input for RecordingInput<I> {
+    fn read_key(&mut self) -> Option<GameKey> {
+        let key = self.inner.read_key();
+        if let Some(key) = key {
+            self.demo.events.push((self.frame, key));
+        }
+        self.frame += 1;
+        key
+    }
+}
+
+/// Plays back a demo, delivering at most one key per frame.
+pub struct ReplayInput {
+    events: VecDeque<(usize, GameKey)>,
+    frame: usize,
+}
+
+impl ReplayInput {
+    pub fn new(demo: Demo) -> Self {
+        ReplayInput {
+            events: demo.events.into(),
+            frame: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl Input for ReplayInput {
+    fn read_key(&mut self) -> Option<GameKey> {
+        let key = match self.events.front() {
+            Some((frame, _)) if *frame <= self.frame => self.events.pop_front().map(|(_, key)| key),
+            _ => None,
+        };
+        self.frame += 1;
+        key
+    }
+}
@@ -0,0 +1,51 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::validate::{Diagnostic, Severity};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssetError {
+    NotFound { file: String },
+    NotARegularFile { file: String },
+    TooLarge { file: String, max_size: usize },
+    Io { file: String, message: &'static str },
+    BadBmp { file: String, message: String },
+    BadJson { file: String, message: String },
+    BadLevel { diagnostics: Vec<Diagnostic> },
+    BadDemo { file: String, message: String },
+}
+
+impl AssetError {
+    // The path of a file as passed to AssetSource::read_file, for error messages.
+    pub fn path(file_name: &str, directory: Option<&str>) -> String {
+        match directory {
+            Some(directory) => format!("{}/{}", directory, file_name),
+            None => String::from(file_name),
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::NotFound { file } => write!(f, "{}: file not found", file),
+            AssetError::NotARegularFile { file } => write!(f, "{}: not a regular file", file),
+            AssetError::TooLarge { file, max_size } => write!(f, "{}: file is larger than {} bytes", file, max_size),
+            AssetError::Io { file, message } => write!(f, "{}: {}", file, message),
+            AssetError::BadBmp { file, message } => write!(f, "{}: not a valid 24 bit BMP: {}", file, message),
+            AssetError::BadJson { file, message } => write!(f, "{}: {}", file, message),
+            AssetError::BadLevel { diagnostics } => {
+                let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error);
+                for (i, diagnostic) in errors.enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            AssetError::BadDemo { file, message } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use crate::error::AssetError;
+use crate::graphics::{Camera, DrawnSprite, EntityLoader, Level, LevelPack, Movement, PackLevel, Tile, TileSet, VirtualFrameBuffer};
+use crate::math::{Color4, Vec2};
+use crate::platform::{AssetSource, Clock, Display, GameKey, Input};
+
+/*
+ * The levels are the *.level and *.lvl files in the levels directory, played in the
+ * order of their names: numbers first and by value (so 10 comes after 9), everything
+ * else after them in alphabetical order.
+ */
+pub fn level_names(assets: &dyn AssetSource) -> Result<Vec<String>, AssetError> {
+    let mut names: Vec<String> = assets
+        .list_directory("levels")?
+        .iter()
+        .filter_map(|file_name| file_name.strip_suffix(".lvl").or_else(|| file_name.strip_suffix(".level")))
+        .map(|name| name.to_string())
+        .collect();
+
+    names.sort_by(|a, b| match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a_num), Ok(b_num)) => a_num.cmp(&b_num).then_with(|| a.cmp(b)),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    });
+    // A level converted to a .level file may still have its .lvl files around.
+    names.dedup();
+    Ok(names)
+}
+
+pub struct Game {
+    pub pack: LevelPack,
+    pub level_index: usize,
+    pub tile_set: TileSet,
+    pub entity_loader: EntityLoader,
+    pub level: Level,
+    pub move_dir: Vec2,
+    // Tiles walked and SPACE presses in the current level, what par_moves counts.
+    pub moves: usize,
+    // The moves of every level solved so far.
+    pub solved_moves: Vec<usize>,
+    // Set once the treasure of the last level is reached.
+    pub escaped: bool,
+    // What the last render left in the buffer, None to redraw all of it.
+    drawn: Option<DrawnLevel>,
+    // Walked distance that doesn't add up to a pixel yet, in 1 / STEPS_PER_SECOND pixels.
+    move_progress: u64,
+    // Grid movement: pixels left to slide to the next tile, and a key pressed during the slide.
+    slide_pixels: i32,
+    pending_key: Option<GameKey>,
+    // Grid movement: the crate that slides along in front of the player.
+    pushed_crate: Option<usize>,
+}
+
+struct DrawnLevel {
+    camera: (i32, i32),
+    worn_mask: Option<usize>,
+    sprites: Vec<DrawnSprite>,
+}
+
+impl Game {
+    // The game logic runs in fixed steps, one Input::read_key and update each.
+    pub const STEPS_PER_SECOND: u64 = 100;
+    // How fast the player walks.
+    pub const PIXELS_PER_SECOND: u64 = 100;
+    // Grid movement: how many steps the slide from one tile to the next takes.
+    pub const SLIDE_STEPS: usize = 8;
+
+    pub fn new(assets: &dyn AssetSource) -> Result<Self, AssetError> {
+        Self::new_at_level(assets, 0)
+    }
+
+    // Starts at pack.levels[level_index], which panics if there are not that many levels.
+    pub fn new_at_level(assets: &dyn AssetSource, level_index: usize) -> Result<Self, AssetError> {
+        let pack = LevelPack::new(assets)?;
+        if pack.levels.is_empty() {
+            return Err(AssetError::NotFound { file: String::from("levels/*.lvl") });
+        }
+
+        let pack_level = &pack.levels[level_index];
+        let tile_set = TileSet::new_from_file(assets, pack_level.tile_set_file())?;
+        let entity_loader = EntityLoader::new_from_file(assets, pack_level.entities_file())?;
+        entity_loader.check_tiles(&tile_set)?;
+        let level = Level::new_from_name(assets, &entity_loader, &pack_level.level)?;
+
+        Ok(Game {
+            pack,
+            level_index,
+            tile_set,
+            entity_loader,
+            level,
+            move_dir: Vec2::new(0, 0),
+            moves: 0,
+            solved_moves: vec![],
+            escaped: false,
+            drawn: None,
+            move_progress: 0,
+            slide_pixels: 0,
+            pending_key: None,
+            pushed_crate: None,
+        })
+    }
+
+    // Steps it takes to walk from one tile to the next, from a standstill.
+    pub fn steps_per_tile() -> usize {
+        ((Tile::WIDTH as u64 * Self::STEPS_PER_SECOND + Self::PIXELS_PER_SECOND - 1) / Self::PIXELS_PER_SECOND) as usize
+    }
+
+    pub fn pack_level(&self) -> &PackLevel {
+        &self.pack.levels[self.level_index]
+    }
+
+    pub fn level_name(&self) -> &str {
+        &self.pack_level().level
+    }
+
+    fn next_level(&mut self, assets: &dyn AssetSource) -> Result<(), AssetError> {
+        let previous = self.pack_level().clone();
+        self.level_index += 1;
+        let pack_level = self.pack_level().clone();
+
+        // Only reload what this level overrides differently than the one before.
+        if pack_level.tile_set_file() != previous.tile_set_file() {
+            self.tile_set = TileSet::new_from_file(assets, pack_level.tile_set_file())?;
+        }
+        if pack_level.entities_file() != previous.entities_file() {
+            self.entity_loader = EntityLoader::new_from_file(assets, pack_level.entities_file())?;
+        }
+        self.entity_loader.check_tiles(&self.tile_set)?;
+
+        self.level = Level::new_from_name(assets, &self.entity_loader, &pack_level.level)?;
+        self.moves = 0;
+        self.drawn = None;
+        self.move_progress = 0;
+        self.slide_pixels = 0;
+        self.pending_key = None;
+        self.pushed_crate = None;
+        Ok(())
+    }
+
+    pub fn movement(&self) -> Movement {
+        self.pack.movement
+    }
+
+    pub fn handle_key(&mut self, key: GameKey) {
+        if self.movement() == Movement::Grid {
+            self.handle_grid_key(key);
+            return;
+        }
+
+        // Pressing the opposite direction stops the player, any other arrow changes direction.
+        let move_dir = self.move_dir;
+        if key != GameKey::Action && key != GameKey::Cycle {
+            self.move_progress = 0;
+        }
+        match key {
+            GameKey::Left => {
+                self.move_dir = if move_dir[0] == 1 {
+                    Vec2::new(0, 0)
+                } else {
+                    Vec2::new(-1, 0)
+                };
+            }
+            GameKey::Right => {
+                self.move_dir = if move_dir[0] == -1 {
+                    Vec2::new(0, 0)
+                } else {
+                    Vec2::new(1, 0)
+                };
+            }
+            GameKey::Up => {
+                self.move_dir = if move_dir[1] == 1 {
+                    Vec2::new(0, 0)
+                } else {
+                    Vec2::new(0, -1)
+                };
+            }
+            GameKey::Down => {
+                self.move_dir = if move_dir[1] == -1 {
+                    Vec2::new(0, 0)
+                } else {
+                    Vec2::new(0, 1)
+                };
+            }
+            GameKey::Action => {
+                self.level.swap_mask(&self.entity_loader);
+                self.moves += 1;
+            }
+            GameKey::Cycle => {
+                self.level.player.cycle_mask();
+                self.moves += 1;
+            }
+        }
+    }
+
+    /*
+     * Grid movement: an arrow starts a slide onto the next tile if it can be entered, a crate
+     * on it slides along. The tile counts as a move right away. Keys pressed during a slide
+     * wait for its end, only the last one is kept.
+     */
+    fn handle_grid_key(&mut self, key: GameKey) {
+        if self.slide_pixels > 0 {
+            self.pending_key = Some(key);
+            return;
+        }
+
+        let move_dir = match key {
+            GameKey::Left => Vec2::new(-1, 0),
+            GameKey::Right => Vec2::new(1, 0),
+            GameKey::Up => Vec2::new(0, -1),
+            GameKey::Down => Vec2::new(0, 1),
+            GameKey::Action => {
+                self.level.swap_mask(&self.entity_loader);
+                self.moves += 1;
+                return;
+            }
+            GameKey::Cycle => {
+                self.level.player.cycle_mask();
+                self.moves += 1;
+                return;
+            }
+        };
+        if self.level.can_step(move_dir) {
+            self.pushed_crate = self.level.crate_at_step(move_dir);
+            self.move_dir = move_dir;
+            self.slide_pixels = Tile::WIDTH as i32;
+            self.moves += 1;
+        }
+    }
+
+    // Moves the player a bit further towards the next tile, the whole tile takes SLIDE_STEPS steps.
+    fn slide(&mut self) {
+        if self.slide_pixels == 0 {
+            if let Some(key) = self.pending_key.take() {
+                self.handle_grid_key(key);
+            }
+        }
+        if self.slide_pixels == 0 {
+            return;
+        }
+
+        let pixels = (Tile::WIDTH / Self::SLIDE_STEPS) as i32;
+        let pixels = pixels.min(self.slide_pixels);
+        let slide = Vec2::new(self.move_dir[0] * pixels, self.move_dir[1] * pixels);
+        self.level.player.sprite.pos += slide;
+        if let Some(index) = self.pushed_crate {
+            self.level.crates[index].sprite.pos += slide;
+        }
+        self.slide_pixels -= pixels;
+        if self.slide_pixels == 0 {
+            self.move_dir = Vec2::new(0, 0);
+            self.pushed_crate = None;
+        }
+    }
+
+    pub fn update(&mut self, assets: &dyn AssetSource) -> Result<(), AssetError> {
+        if self.escaped {
+            return Ok(());
+        }
+
+        if self.movement() == Movement::Grid {
+            self.slide();
+        } else {
+            self.walk();
+        }
+
+        if self.level.is_solved() {
+            self.move_dir = Vec2::new(0, 0);
+            self.solved_moves.push(self.moves);
+            if self.level_index + 1 == self.pack.levels.len() {
+                self.escaped = true;
+            } else {
+                self.next_level(assets)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Free movement
+    fn walk(&mut self) {
+        // The player walks one pixel at a time, at most one per step, see below.
+        self.move_progress += Self::PIXELS_PER_SECOND;
+        if self.move_progress >= Self::STEPS_PER_SECOND {
+            self.move_progress -= Self::STEPS_PER_SECOND;
+
+            let old_pos = self.level.player.sprite.pos;
+            self.level.move_player(self.move_dir);
+
+            // A move starts when the player leaves a tile, the last one ends half way on the treasure.
+            let pos = self.level.player.sprite.pos;
+            let old_index_pos = old_pos - self.level.sprite.pos;
+            let moved = pos[0] != old_pos[0] || pos[1] != old_pos[1];
+            if moved && old_index_pos[0] % Tile::WIDTH as i32 == 0 && old_index_pos[1] % Tile::HEIGHT as i32 == 0 {
+                self.moves += 1;
+            }
+        }
+    }
+
+    /*
+     * Only redraws the tiles of sprites that moved or changed since the last render and
+     * marks them dirty in the buffer. Everything is redrawn when the camera moved, when
+     * the worn mask changed (it reveals hidden tiles, see Entity::revealed) and after a
+     * new level was loaded. Expects the same buffer on every call.
+     */
+    pub fn render(&mut self, vfb: &mut VirtualFrameBuffer) {
+        let black = Color4::new(0, 0, 0, 255);
+
+        // The backends tell the player they escaped, the mansion is gone.
+        if self.escaped {
+            if self.drawn.take().is_some() {
+                vfb.clear(black);
+            }
+            return;
+        }
+
+        let camera = Camera::new_following(&self.level, vfb.width(), vfb.height());
+        let sprites = self.level.drawn_sprites(&camera);
+        let worn_mask = self.level.player.worn_mask();
+        match self.drawn.as_ref() {
+            Some(drawn) if drawn.camera == (camera.pos[0], camera.pos[1]) && drawn.worn_mask == worn_mask => {
+                let changed = drawn
+                    .sprites
+                    .iter()
+                    .filter(|sprite| !sprites.contains(sprite))
+                    .chain(sprites.iter().filter(|sprite| !drawn.sprites.contains(sprite)));
+                for sprite in changed {
+                    vfb.fill_rect(sprite.rect, black);
+                    self.level.draw_clipped(&self.tile_set, vfb, &camera, sprite.rect);
+                }
+            }
+            _ => {
+                vfb.clear(black);
+                self.level.draw_with_camera(&self.tile_set, vfb, &camera);
+            }
+        }
+
+        self.drawn = Some(DrawnLevel { camera: (camera.pos[0], camera.pos[1]), worn_mask, sprites });
+    }
+
+    // One fixed step of the game logic: input, then update.
+    pub fn tick(&mut self, assets: &dyn AssetSource, input: &mut dyn Input) -> Result<(), AssetError> {
+        if let Some(key) = input.read_key() {
+            if !self.escaped {
+                self.handle_key(key);
+            }
+        }
+
+        self.update(assets)
+    }
+
+    /// Runs one iteration of the game loop with a single step: input, update, render.
+    pub fn step(&mut self, assets: &dyn AssetSource, input: &mut dyn Input, display: &mut dyn Display, vfb: &mut VirtualFrameBuffer) -> Result<(), AssetError> {
+        self.run_frame(assets, input, display, vfb, 1)
+    }
+
+    /*
+     * Runs one frame: up to steps fixed steps, then renders and presents the result once.
+     * The frame ends early when a level is solved, so the backend can show the next one.
+     */
+    pub fn run_frame(&mut self, assets: &dyn AssetSource, input: &mut dyn Input, display: &mut dyn Display, vfb: &mut VirtualFrameBuffer, steps: u64) -> Result<(), AssetError> {
+        let level_index = self.level_index;
+        for _ in 0..steps {
+            self.tick(assets, input)?;
+            if self.escaped || self.level_index != level_index {
+                break;
+            }
+        }
+
+        self.render(vfb);
+        display.present(vfb);
+        vfb.dirty.clear();
+        Ok(())
+    }
+}
+
+// Walking faster would skip pixels, collisions and tile aligned stops rely on every one of them.
+#[allow(clippy::assertions_on_constants)]
+const _: () = assert!(Game::PIXELS_PER_SECOND <= Game::STEPS_PER_SECOND);
+
+/*
+ * Turns the time of a monotonic clock into fixed steps of the game logic: every frame
+ * runs the steps that became due since the last one, so the game runs at the same speed
+ * however long rendering takes. After a long stall the game doesn't try to catch up with
+ * more than MAX_STEPS_PER_FRAME steps, it just runs slower for that frame.
+ */
+pub struct StepTimer {
+    last_micros: u64,
+    // Time that doesn't add up to a whole step yet
+    pending_micros: u64,
+}
+
+impl StepTimer {
+    pub const MAX_STEPS_PER_FRAME: u64 = 10;
+    const MICROS_PER_STEP: u64 = 1_000_000 / Game::STEPS_PER_SECOND;
+
+    pub fn new(clock: &dyn Clock) -> Self {
+        StepTimer {
+            last_micros: clock.now_micros(),
+            pending_micros: 0,
+        }
+    }
+
+    // Forgets the time since the last frame, after the game was paused.
+    pub fn reset(&mut self, clock: &dyn Clock) {
+        self.last_micros = clock.now_micros();
+        self.pending_micros = 0;
+    }
+
+    pub fn due_steps(&mut self, clock: &dyn Clock) -> u64 {
+        let now = clock.now_micros();
+        self.pending_micros += now.saturating_sub(self.last_micros);
+        self.last_micros = now;
+
+        let steps = self.pending_micros / Self::MICROS_PER_STEP;
+        self.pending_micros %= Self::MICROS_PER_STEP;
+        steps.min(Self::MAX_STEPS_PER_FRAME)
+    }
+}
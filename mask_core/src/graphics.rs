@@ -0,0 +1,1336 @@
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use embedded_graphics::geometry::OriginDimensions;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics::prelude::Point;
+use lite_json::{parse_json, JsonValue};
+use tinybmp::{Bmp};
+use crate::error::AssetError;
+use crate::game::level_names;
+use crate::math::{Color4, Vec2};
+use crate::screen::PixelMasks;
+use crate::platform::AssetSource;
+use crate::level_file::{self, Layer};
+use crate::validate::{has_errors, validate_layers};
+use hashbrown::HashMap;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: usize, height: usize) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let end_x = (self.x + self.width as i32).min(other.x + other.width as i32);
+        let end_y = (self.y + self.height as i32).min(other.y + other.height as i32);
+        if end_x <= x || end_y <= y {
+            return None;
+        }
+        Some(Rect::new(x, y, (end_x - x) as usize, (end_y - y) as usize))
+    }
+}
+
+/*
+ * The logical screen as one row after the other. A pixel is packed as 0x00RRGGBB, which
+ * is the memory layout of a PixelFormat::Bgr framebuffer, so rows can often be copied
+ * to the screen as they are.
+ */
+pub struct VirtualFrameBuffer {
+    width: usize,
+    height: usize,
+    pub pixels: Vec<u32>,
+    // What changed since the last Display::present, only these parts have to be shown again.
+    pub dirty: Vec<Rect>,
+}
+
+impl VirtualFrameBuffer {
+    // The logical screen, see screen::Letterbox for how it is shown on larger ones.
+    pub const WIDTH: usize = 640;
+    pub const HEIGHT: usize = 480;
+    pub const MASKS: PixelMasks = PixelMasks::BGR;
+
+    pub fn new() -> VirtualFrameBuffer {
+        VirtualFrameBuffer {
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+            pixels: vec![0; Self::WIDTH * Self::HEIGHT],
+            // Nothing of it is on the screen yet.
+            dirty: vec![Rect::new(0, 0, Self::WIDTH, Self::HEIGHT)],
+        }
+    }
+
+    pub fn pack(color: Color4) -> u32 {
+        Self::MASKS.pack(color)
+    }
+
+    pub fn unpack(pixel: u32) -> Color4 {
+        Color4::new((pixel >> 16 & 0xff) as i32, (pixel >> 8 & 0xff) as i32, (pixel & 0xff) as i32, 255)
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width(), self.height())
+    }
+
+    pub fn row(&self, y: usize) -> &[u32] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [u32] {
+        &mut self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn clear(&mut self, clear_color: Color4) {
+        self.fill_rect(self.rect(), clear_color);
+    }
+
+    // Fills the part of rect inside the buffer and marks it dirty.
+    pub fn fill_rect(&mut self, rect: Rect, color: Color4) {
+        if let Some(rect) = rect.intersection(&self.rect()) {
+            let pixel = Self::pack(color);
+            for y in rect.y as usize..rect.y as usize + rect.height {
+                self.row_mut(y)[rect.x as usize..rect.x as usize + rect.width].fill(pixel);
+            }
+            self.dirty.push(rect);
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Encodes the buffer as a binary PPM (P6) image.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width(), self.height()).into_bytes();
+        for pixel in self.pixels.iter() {
+            ppm.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8]);
+        }
+        ppm
+    }
+}
+
+impl Default for VirtualFrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait DrawFramebuffer {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn pos(&self) -> Vec2;
+    // Draws the columns xs of row y into row, where row[0] is column xs.start.
+    fn draw_row(&self, tile_set: &TileSet, y: usize, xs: Range<usize>, row: &mut [u32]);
+
+    fn draw(&self, tile_set: &TileSet, vfb: &mut VirtualFrameBuffer) {
+        self.draw_at(tile_set, vfb, Vec2::new(0, 0));
+    }
+
+    // Draws as seen from a camera at camera_pos, only the pixels that end up in the buffer.
+    fn draw_at(&self, tile_set: &TileSet, vfb: &mut VirtualFrameBuffer, camera_pos: Vec2) {
+        self.draw_clipped(tile_set, vfb, camera_pos, vfb.rect());
+    }
+
+    // Like draw_at, but leaves everything outside of clip (in buffer coordinates) alone.
+    fn draw_clipped(&self, tile_set: &TileSet, vfb: &mut VirtualFrameBuffer, camera_pos: Vec2, clip: Rect) {
+        let clip = match clip.intersection(&vfb.rect()) {
+            Some(clip) => clip,
+            None => return,
+        };
+        let p = self.pos() - camera_pos;
+        let visible = |start: i32, size: usize, clip_start: i32, clip_size: usize| {
+            let first = (clip_start - start).max(0) as usize;
+            let end = (clip_start + clip_size as i32 - start).clamp(0, size as i32) as usize;
+            first..end.max(first)
+        };
+
+        let (xs, ys) = (visible(p[0], self.width(), clip.x, clip.width), visible(p[1], self.height(), clip.y, clip.height));
+        // Off to the side of clip the buffer columns below would be outside of the row.
+        if xs.is_empty() || ys.is_empty() {
+            return;
+        }
+        let fb_xs = (p[0] + xs.start as i32) as usize..(p[0] + xs.end as i32) as usize;
+        for y in ys {
+            let row = vfb.row_mut((p[1] + y as i32) as usize);
+            self.draw_row(tile_set, y, xs.clone(), &mut row[fb_xs.clone()]);
+        }
+    }
+}
+
+/*
+ * Pixels are packed like in the VirtualFrameBuffer and indexed [y][x]. Magenta, the color
+ * key of the tile set bitmap, is TRANSPARENT and not drawn.
+ */
+pub struct Tile {
+    pub pixels: [[u32; 16]; 16],
+}
+
+impl Tile {
+    pub const WIDTH: usize = 16;
+    pub const HEIGHT: usize = 16;
+    pub const TRANSPARENT: u32 = 0xff00ff;
+
+    fn new_from_pixels(pixels: [[u32; 16]; 16]) -> Self {
+        Tile {
+            pixels
+        }
+    }
+}
+
+impl Default for Tile {
+    fn default() -> Self {
+        Tile {
+            pixels: [[0xffffff; 16]; 16],
+        }
+    }
+}
+
+pub struct TileSet {
+    pub tiles: Vec<Vec<Tile>>,
+}
+
+impl TileSet {
+    pub const FILE_NAME: &str = "TileSet.bmp";
+
+    pub fn new_from_file(assets: &dyn AssetSource, file_name: &str) -> Result<Self, AssetError> {
+        let buffer = assets.read_file(file_name, None)?;
+        Self::new_from_buffer(file_name, buffer)
+    }
+
+    pub fn new_from_buffer(file_name: &str, buffer: Vec<u8>) -> Result<Self, AssetError> {
+        let bmp = Bmp::<Rgb888>::from_slice(buffer.as_slice())
+            .map_err(|e| AssetError::BadBmp { file: String::from(file_name), message: format!("{:?}", e) })?;
+        let mut tiles: Vec<Vec<Tile>> = vec![];
+        let width_in_tiles = bmp.size().width / 16;
+        let height_in_tiles = bmp.size().height / 16;
+        for tile_y in 0..height_in_tiles {
+            let mut row = vec![];
+            for tile_x in 0..width_in_tiles {
+                let mut tile_bitmap = [[0xffffff; 16]; 16];
+                for x in 0..16 {
+                    for y in 0..16 {
+                        let point = Point::new((tile_x * 16 + x) as i32, (tile_y * 16 + y) as i32);
+                        let pixel = bmp.pixel(point).unwrap();
+                        let color = Color4::new(pixel.r() as i32, pixel.g() as i32, pixel.b() as i32, 255);
+                        tile_bitmap[y as usize][x as usize] = VirtualFrameBuffer::pack(color);
+                    }
+                }
+                row.push(Tile::new_from_pixels(tile_bitmap));
+            }
+            tiles.push(row);
+        }
+
+        Ok(TileSet {
+            tiles,
+        })
+    }
+
+    pub fn contains(&self, tile_x: u8, tile_y: u8) -> bool {
+        (tile_x as usize) < self.tiles.len() && (tile_y as usize) < self.tiles[tile_x as usize].len()
+    }
+}
+
+impl Default for TileSet {
+    fn default() -> Self {
+        TileSet {
+            tiles: vec![vec![Tile::default()]],
+        }
+    }
+}
+
+/*
+ * Which masks let the player walk through a wall, door_colors are the colors the rule
+ * is about:
+ * Any: wearing a mask of one of them (walls without door_colors never open)
+ * All: carrying masks of all of them
+ * Not: not wearing a mask of any of them, also without a mask
+ * Bare: without any mask
+ * Plates: while crates press all plates of the level
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DoorRule {
+    #[default]
+    Any,
+    All,
+    Not,
+    Bare,
+    Plates,
+}
+
+impl DoorRule {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "any" => Some(DoorRule::Any),
+            "all" => Some(DoorRule::All),
+            "not" => Some(DoorRule::Not),
+            "bare" => Some(DoorRule::Bare),
+            "plates" => Some(DoorRule::Plates),
+            _ => None,
+        }
+    }
+}
+
+// What door rules look at: the worn mask, the colors of all carried masks, sorted, and the plates.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WornMasks {
+    pub worn: Option<usize>,
+    pub carried: Vec<usize>,
+    pub plates_pressed: bool,
+}
+
+/*
+ * mask_color makes the entity the look of the masks of that color; they are placed with
+ * the upper case glyph in the items layer, 'R' for the "r" entity. A mask color that has
+ * mixed_from, two other colors, is made from masks of those on a mixer tile.
+ *
+ * A hidden entity looks and acts like the revealed one while the player wears a mask of
+ * one of the revealed_by colors: a wall that is a passage, an illusory wall, a floor
+ * that can't be seen without the mask.
+ *
+ * A pushable entity is a crate, placed like masks with the upper case glyph. Crates block
+ * like walls, whatever their door_colors, and are pushed one tile on by the player if the
+ * tile behind them is free. A crate on every plate tile opens the "plates" doors.
+ */
+#[derive(Clone)]
+pub struct Entity {
+    pub name: String,
+    pub tile_x: u8,
+    pub tile_y: u8,
+    pub wall: bool,
+    pub door_colors: Vec<usize>,
+    pub door_rule: DoorRule,
+    pub mask_color: Option<usize>,
+    pub mixed_from: Vec<usize>,
+    pub mixer: bool,
+    pub revealed_by: Vec<usize>,
+    pub revealed: Option<Box<Entity>>,
+    pub pushable: bool,
+    pub plate: bool,
+}
+
+impl Entity {
+    // What the entity looks and acts like for a player wearing worn_mask.
+    pub fn seen_with(&self, worn_mask: Option<usize>) -> &Entity {
+        match &self.revealed {
+            Some(revealed) if worn_mask.map_or(false, |color| self.revealed_by.contains(&color)) => revealed,
+            _ => self,
+        }
+    }
+
+    // Whether the player can stand on it with some mask.
+    pub fn is_floor(&self) -> bool {
+        !self.wall || self.revealed.as_ref().map_or(false, |revealed| !revealed.wall)
+    }
+
+    // Whether the player can walk through this entity with these masks, the one place door rules are decided.
+    pub fn opens_for(&self, masks: &WornMasks) -> bool {
+        if self.pushable {
+            return false;
+        }
+        if !self.wall {
+            return true;
+        }
+
+        let wears = |color: &usize| masks.worn == Some(*color);
+        match self.door_rule {
+            DoorRule::Any => self.door_colors.iter().any(wears),
+            DoorRule::All => self.door_colors.iter().all(|color| masks.carried.contains(color)),
+            DoorRule::Not => !self.door_colors.iter().any(wears),
+            DoorRule::Bare => masks.worn.is_none(),
+            DoorRule::Plates => masks.plates_pressed,
+        }
+    }
+
+    // Whether the masks of the player can make a difference for walking through this entity.
+    pub fn is_door(&self) -> bool {
+        self.wall && (self.door_rule != DoorRule::Any || !self.door_colors.is_empty())
+    }
+}
+
+impl Default for Entity {
+    fn default() -> Self {
+        Entity {
+            name: String::new(),
+            tile_x: 0,
+            tile_y: 0,
+            wall: false,
+            door_colors: vec![],
+            door_rule: DoorRule::Any,
+            mask_color: None,
+            mixed_from: vec![],
+            mixer: false,
+            revealed_by: vec![],
+            revealed: None,
+            pushable: false,
+            plate: false,
+        }
+    }
+}
+
+
+pub struct Sprite {
+    pub pos: Vec2,
+    pub entities: Vec<Vec<Entity>>,
+}
+
+impl Sprite {
+    pub fn new(entities: Vec<Vec<Entity>>) -> Self {
+        Sprite {
+            pos: Vec2::new(0, 0),
+            entities,
+        }
+    }
+
+    pub fn tiles_width(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn tiles_height(&self) -> usize {
+        self.entities[0].len()
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.pos[0], self.pos[1], self.width(), self.height())
+    }
+
+    pub fn collides(&self, sprite: &Sprite) -> bool {
+        let (s1_start_x, s1_start_y) = (self.pos[0], self.pos[1]);
+        let (s1_end_x, s1_end_y) = (self.pos[0] + self.width() as i32, self.pos[1] + self.height() as i32);
+
+        let (s2_start_x, s2_start_y) = (sprite.pos[0], sprite.pos[1]);
+        let (s2_end_x, s2_end_y) = (sprite.pos[0] + sprite.width() as i32, sprite.pos[1] + sprite.height() as i32);
+
+        ((s1_start_x >= s2_start_x && s1_start_x < s2_end_x) || (s1_end_x > s2_start_x && s1_end_x <= s2_end_x))
+            && ((s1_start_y >= s2_start_y && s1_start_y < s2_end_y) || (s1_end_y > s2_start_y && s1_end_y <= s2_end_y))
+    }
+
+    fn draw_row_seen_with(&self, tile_set: &TileSet, y: usize, xs: Range<usize>, row: &mut [u32], worn_mask: Option<usize>) {
+        let (tile_y, pixel_y) = (y / Tile::HEIGHT, y % Tile::HEIGHT);
+        let mut x = xs.start;
+        while x < xs.end {
+            // The rest of this tile's row, or of xs if that ends first.
+            let (tile_x, pixel_x) = (x / Tile::WIDTH, x % Tile::WIDTH);
+            let count = (Tile::WIDTH - pixel_x).min(xs.end - x);
+
+            let entity = self.entities[tile_x][tile_y].seen_with(worn_mask);
+            let tile = &tile_set.tiles[entity.tile_x as usize][entity.tile_y as usize];
+            let source = &tile.pixels[pixel_y][pixel_x..pixel_x + count];
+            let target = &mut row[x - xs.start..x - xs.start + count];
+            for (target, pixel) in target.iter_mut().zip(source) {
+                if *pixel != Tile::TRANSPARENT {
+                    *target = *pixel;
+                }
+            }
+            x += count;
+        }
+    }
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Sprite {
+            pos: Vec2::new(0, 0),
+            entities: vec![vec![Entity::default()]],
+        }
+    }
+}
+
+impl DrawFramebuffer for Sprite {
+    fn width(&self) -> usize {
+        self.entities.len() * Tile::WIDTH
+    }
+
+    fn height(&self) -> usize {
+        self.entities[0].len() * Tile::HEIGHT
+    }
+
+    fn pos(&self) -> Vec2 {
+        self.pos
+    }
+
+    fn draw_row(&self, tile_set: &TileSet, y: usize, xs: Range<usize>, row: &mut [u32]) {
+        self.draw_row_seen_with(tile_set, y, xs, row, None);
+    }
+}
+
+// A sprite as seen by a player wearing worn_mask, with its hidden entities revealed by it.
+pub struct RevealedSprite<'a> {
+    pub sprite: &'a Sprite,
+    pub worn_mask: Option<usize>,
+}
+
+impl<'a> DrawFramebuffer for RevealedSprite<'a> {
+    fn width(&self) -> usize {
+        self.sprite.width()
+    }
+
+    fn height(&self) -> usize {
+        self.sprite.height()
+    }
+
+    fn pos(&self) -> Vec2 {
+        self.sprite.pos
+    }
+
+    fn draw_row(&self, tile_set: &TileSet, y: usize, xs: Range<usize>, row: &mut [u32]) {
+        self.sprite.draw_row_seen_with(tile_set, y, xs, row, self.worn_mask);
+    }
+}
+
+/*
+ * The player carries up to inventory_size masks and wears one of them, masks[worn]. A
+ * mask that is taken is put on in the slot of the worn one, the others move one slot up;
+ * Cycle puts on the next one. Dropping the worn mask puts on the one after it.
+ */
+pub struct Player {
+    pub sprite: Sprite,
+    pub masks: Vec<usize>,
+    pub worn: usize,
+    pub inventory_size: usize,
+}
+
+impl Player {
+    pub const DEFAULT_INVENTORY_SIZE: usize = 1;
+
+    pub fn new(entity_loader: &EntityLoader) -> Result<Self, AssetError> {
+        let entity = entity_loader.get("P")?.clone();
+        let sprite = Sprite::new(vec![vec![entity]]);
+
+        Ok(Player {
+            sprite,
+            masks: vec![],
+            worn: 0,
+            inventory_size: Self::DEFAULT_INVENTORY_SIZE,
+        })
+    }
+
+    pub fn worn_mask(&self) -> Option<usize> {
+        self.masks.get(self.worn).copied()
+    }
+
+    // The plates are up to the level, see Level::worn_masks.
+    pub fn worn_masks(&self) -> WornMasks {
+        let mut carried = self.masks.clone();
+        carried.sort_unstable();
+        carried.dedup();
+        WornMasks { worn: self.worn_mask(), carried, plates_pressed: false }
+    }
+
+    // Puts on the mask, if the inventory is full it replaces the worn one, which is returned.
+    pub fn take_mask(&mut self, mask_color: usize) -> Option<usize> {
+        if self.masks.len() < self.inventory_size {
+            self.masks.insert(self.worn, mask_color);
+            None
+        } else {
+            Some(core::mem::replace(&mut self.masks[self.worn], mask_color))
+        }
+    }
+
+    pub fn drop_mask(&mut self) -> Option<usize> {
+        if self.masks.is_empty() {
+            return None;
+        }
+
+        let mask_color = self.masks.remove(self.worn);
+        if self.worn == self.masks.len() {
+            self.worn = 0;
+        }
+        Some(mask_color)
+    }
+
+    pub fn cycle_mask(&mut self) {
+        if !self.masks.is_empty() {
+            self.worn = (self.worn + 1) % self.masks.len();
+        }
+    }
+}
+
+pub struct Mask {
+    pub sprite: Sprite,
+    pub mask_color: usize,
+}
+
+impl Mask {
+    pub fn new_from_color_id(color: usize, entity_loader: &EntityLoader) -> Self {
+        let entity = entity_loader.mask_entity(color).map(|(_, entity)| entity.clone()).unwrap_or_default();
+        let sprite = Sprite::new(vec![vec![entity]]);
+
+        Mask {
+            sprite,
+            mask_color: color,
+        }
+    }
+}
+
+pub struct Treasure {
+    pub sprite: Sprite,
+}
+
+impl Treasure {
+    pub fn new(entity_loader: &EntityLoader) -> Result<Self, AssetError> {
+        let sprite = Sprite::new(vec![vec![entity_loader.get("T")?.clone()]]);
+
+        Ok(Treasure {
+            sprite,
+        })
+    }
+}
+
+
+pub struct Crate {
+    pub sprite: Sprite,
+}
+
+impl Crate {
+    pub fn new(entity: &Entity) -> Self {
+        Crate {
+            sprite: Sprite::new(vec![vec![entity.clone()]]),
+        }
+    }
+}
+
+
+pub struct Level {
+    pub sprite: Sprite,
+    pub player: Player,
+    pub masks: Vec<Mask>,
+    pub treasure: Treasure,
+    pub crates: Vec<Crate>,
+    // The number of plate tiles, they stay where they are.
+    pub plates: usize,
+    pub mixes: Vec<MaskMix>,
+}
+
+// Two mask colors and the color of the mask a mixer tile makes of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaskMix {
+    pub colors: [usize; 2],
+    pub mix: usize,
+}
+
+pub struct EntityLoader {
+    pub file_name: String,
+    pub entities: HashMap<String, Entity>,
+    pub mixes: Vec<MaskMix>,
+}
+
+impl EntityLoader {
+    pub const FILE_NAME: &str = "entities.json";
+    // Entities the game creates by itself, not only through levels.
+    const REQUIRED_ENTITIES: [&str; 5] = ["P", "T", "r", "g", "b"];
+    // Mask colors of entity files that don't give them.
+    const DEFAULT_MASK_COLORS: [(&str, usize); 3] = [("r", 0), ("g", 1), ("b", 2)];
+
+    pub fn new(assets: &dyn AssetSource) -> Result<Self, AssetError> {
+        Self::new_from_file(assets, Self::FILE_NAME)
+    }
+
+    pub fn new_from_file(assets: &dyn AssetSource, file_name: &str) -> Result<Self, AssetError> {
+        let bad_json = |message: String| AssetError::BadJson { file: String::from(file_name), message };
+
+        let file_byes = assets.read_file(file_name, None)?;
+        let file_content_str = core::str::from_utf8(&file_byes[..]).map_err(|_| bad_json(String::from("file is not valid UTF-8")))?;
+        let json = parse_json(file_content_str).map_err(|_| bad_json(String::from("file is not valid JSON")))?;
+        let obj = json.as_object().ok_or_else(|| bad_json(String::from("expected an object mapping glyphs to entities")))?;
+        let mut entity_map = HashMap::new();
+        // Hidden entities and the ids of the entities they reveal
+        let mut revealed_ids = vec![];
+        for (entity_key, entity_value) in obj {
+            let entity_str = entity_key.iter().map(|c| c.to_string()).collect::<Vec<String>>().join("");
+            let invalid = |what: &str| bad_json(format!("entity '{}': {}", entity_str, what));
+            let inner_obj = entity_value.as_object().ok_or_else(|| invalid("expected an object"))?;
+            let colors = |key_str: &str, value: &JsonValue| -> Result<Vec<usize>, AssetError> {
+                value.as_array().ok_or_else(|| invalid(&format!("{} is not an array", key_str)))?
+                    .iter()
+                    .map(|item| item.as_number().map(|number| number.integer as usize).ok_or_else(|| invalid(&format!("{} contains a non-number", key_str))))
+                    .collect()
+            };
+            let mut entity = Entity::default();
+            for (key, value) in inner_obj {
+                let key_str = key.iter().map(|c| c.to_string()).collect::<Vec<String>>().join("");
+                if key_str == "name" {
+                    entity.name = value.as_string().ok_or_else(|| invalid("name is not a string"))?.iter().collect();
+                }
+                if key_str == "tile_x" {
+                    entity.tile_x = value.as_number().ok_or_else(|| invalid("tile_x is not a number"))?.integer as u8;
+                }
+                if key_str == "tile_y" {
+                    entity.tile_y = value.as_number().ok_or_else(|| invalid("tile_y is not a number"))?.integer as u8;
+                }
+                if key_str == "wall" {
+                    entity.wall = value.as_bool().ok_or_else(|| invalid("wall is not a boolean"))?.to_owned();
+                }
+
+                if key_str == "door_colors" {
+                    entity.door_colors = colors(&key_str, value)?;
+                }
+                if key_str == "door_rule" {
+                    let rule_name: String = value.as_string().ok_or_else(|| invalid("door_rule is not a string"))?.iter().collect();
+                    entity.door_rule = DoorRule::from_name(&rule_name)
+                        .ok_or_else(|| invalid(&format!("unknown door_rule '{}', expected \"any\", \"all\", \"not\", \"bare\" or \"plates\"", rule_name)))?;
+                }
+                if key_str == "mask_color" {
+                    entity.mask_color = Some(value.as_number().ok_or_else(|| invalid("mask_color is not a number"))?.integer as usize);
+                }
+                if key_str == "mixed_from" {
+                    entity.mixed_from = colors(&key_str, value)?;
+                }
+                if key_str == "mixer" {
+                    entity.mixer = value.as_bool().ok_or_else(|| invalid("mixer is not a boolean"))?.to_owned();
+                }
+                if key_str == "revealed_by" {
+                    entity.revealed_by = colors(&key_str, value)?;
+                }
+                if key_str == "pushable" {
+                    entity.pushable = value.as_bool().ok_or_else(|| invalid("pushable is not a boolean"))?.to_owned();
+                }
+                if key_str == "plate" {
+                    entity.plate = value.as_bool().ok_or_else(|| invalid("plate is not a boolean"))?.to_owned();
+                }
+                if key_str == "revealed" {
+                    let revealed_id: String = value.as_string().ok_or_else(|| invalid("revealed is not a string"))?.iter().collect();
+                    revealed_ids.push((entity_str.clone(), revealed_id));
+                }
+            }
+            let has_revealed = revealed_ids.last().map_or(false, |(id, _)| *id == entity_str);
+            if entity.revealed_by.is_empty() == has_revealed {
+                return Err(invalid("revealed and revealed_by need each other"));
+            }
+            if !entity.mixed_from.is_empty() && (entity.mixed_from.len() != 2 || entity.mask_color.is_none()) {
+                return Err(invalid("mixed_from needs two colors and a mask_color to mix them to"));
+            }
+            entity_map.insert(entity_str, entity);
+        }
+
+        for id in Self::REQUIRED_ENTITIES {
+            if !entity_map.contains_key(id) {
+                return Err(bad_json(format!("entity '{}' is missing", id)));
+            }
+        }
+        for (id, color) in Self::DEFAULT_MASK_COLORS {
+            let entity = entity_map.get_mut(id).unwrap();
+            entity.mask_color = entity.mask_color.or(Some(color));
+        }
+
+        // Every color has one look, see mask_entity.
+        let mut ids: Vec<&String> = entity_map.keys().collect();
+        ids.sort();
+        let mut color_ids = HashMap::new();
+        for id in ids {
+            if let Some(color) = entity_map[id].mask_color {
+                if let Some(other) = color_ids.insert(color, id) {
+                    return Err(bad_json(format!("entity '{}': mask_color {} is already the color of entity '{}'", id, color, other)));
+                }
+            }
+        }
+
+        // Revealed entities are copied as they are, their own hidden entity isn't revealed.
+        for (id, revealed_id) in revealed_ids {
+            let revealed = entity_map.get(&revealed_id)
+                .ok_or_else(|| bad_json(format!("entity '{}': revealed entity '{}' is missing", id, revealed_id)))?
+                .clone();
+            entity_map.get_mut(&id).unwrap().revealed = Some(Box::new(Entity { revealed: None, ..revealed }));
+        }
+
+        let mixes = entity_map.values()
+            .filter(|entity| !entity.mixed_from.is_empty())
+            .map(|entity| MaskMix { colors: [entity.mixed_from[0], entity.mixed_from[1]], mix: entity.mask_color.unwrap() })
+            .collect();
+
+        Ok(EntityLoader {
+            file_name: String::from(file_name),
+            entities: entity_map,
+            mixes,
+        })
+    }
+
+    pub fn check_tiles(&self, tile_set: &TileSet) -> Result<(), AssetError> {
+        for (id, entity) in self.entities.iter() {
+            if !tile_set.contains(entity.tile_x, entity.tile_y) {
+                return Err(AssetError::BadJson {
+                    file: self.file_name.clone(),
+                    message: format!("entity '{}' uses tile ({}, {}), which is outside of the tile set", id, entity.tile_x, entity.tile_y),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<&Entity, AssetError> {
+        self.entities.get(id).ok_or_else(|| AssetError::BadJson { file: self.file_name.clone(), message: format!("entity '{}' is missing", id) })
+    }
+
+    // The entity that masks of this color look like, and its id, there is only one.
+    pub fn mask_entity(&self, color: usize) -> Option<(&String, &Entity)> {
+        self.entities.iter().find(|(_, entity)| entity.mask_color == Some(color))
+    }
+
+    // The entity an upper case item glyph places, the one of its lower case glyph.
+    pub fn item_entity(&self, item: char) -> Option<&Entity> {
+        if !item.is_uppercase() {
+            return None;
+        }
+        self.entities.get(&item.to_lowercase().to_string())
+    }
+
+    // The color of the mask an item glyph places, if it places one.
+    pub fn mask_color_from_item(&self, item: char) -> Option<usize> {
+        self.item_entity(item).and_then(|entity| entity.mask_color)
+    }
+}
+
+/*
+ * pack.json lists the levels in the order they are played and what the game tells the
+ * player about them. Only "level", the name of the level in the levels directory, is
+ * required; "tile_set" and "entities" replace TileSet.bmp and entities.json for one level.
+ * "movement" is "free" (the default) or "grid", see Movement.
+ *
+ * {
+ *   "name": "The magical mansion of Maunz",
+ *   "movement": "grid",
+ *   "levels": [
+ *     { "level": "0", "name": "The hall", "intro": "Find the treasure!", "par_moves": 18 },
+ *     { "level": "1", "tile_set": "Cellar.bmp", "entities": "cellar.json" }
+ *   ]
+ * }
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackLevel {
+    pub level: String,
+    pub name: Option<String>,
+    pub intro: Option<String>,
+    pub par_moves: Option<usize>,
+    pub tile_set: Option<String>,
+    pub entities: Option<String>,
+}
+
+impl PackLevel {
+    pub fn tile_set_file(&self) -> &str {
+        self.tile_set.as_deref().unwrap_or(TileSet::FILE_NAME)
+    }
+
+    pub fn entities_file(&self) -> &str {
+        self.entities.as_deref().unwrap_or(EntityLoader::FILE_NAME)
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.level)
+    }
+}
+
+/*
+ * Free: the player walks pixel by pixel until the opposite arrow is pressed.
+ * Grid: every arrow press moves the player exactly one tile, if that tile can be entered.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Movement {
+    #[default]
+    Free,
+    Grid,
+}
+
+impl Movement {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "free" => Some(Movement::Free),
+            "grid" => Some(Movement::Grid),
+            _ => None,
+        }
+    }
+}
+
+pub struct LevelPack {
+    pub name: Option<String>,
+    pub movement: Movement,
+    pub levels: Vec<PackLevel>,
+}
+
+impl LevelPack {
+    pub const FILE_NAME: &str = "pack.json";
+
+    // Without a pack.json every level in the levels directory is played, see game::level_names.
+    pub fn new(assets: &dyn AssetSource) -> Result<Self, AssetError> {
+        match assets.read_file(Self::FILE_NAME, None) {
+            Ok(buffer) => Self::new_from_buffer(Self::FILE_NAME, &buffer),
+            Err(AssetError::NotFound { .. }) => Ok(LevelPack {
+                name: None,
+                movement: Movement::Free,
+                levels: level_names(assets)?
+                    .into_iter()
+                    .map(|level| PackLevel { level, ..PackLevel::default() })
+                    .collect(),
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn new_from_buffer(file_name: &str, buffer: &[u8]) -> Result<Self, AssetError> {
+        let bad_json = |message: String| AssetError::BadJson { file: String::from(file_name), message };
+
+        let file_content_str = core::str::from_utf8(buffer).map_err(|_| bad_json(String::from("file is not valid UTF-8")))?;
+        let json = parse_json(file_content_str).map_err(|_| bad_json(String::from("file is not valid JSON")))?;
+        let obj = json.as_object().ok_or_else(|| bad_json(String::from("expected an object with a list of levels")))?;
+
+        let mut name = None;
+        let mut movement = Movement::Free;
+        let mut levels = vec![];
+        for (key, value) in obj.iter() {
+            let key_str: String = key.iter().collect();
+            if key_str == "name" {
+                name = Some(value.as_string().ok_or_else(|| bad_json(String::from("name is not a string")))?.iter().collect());
+            }
+            if key_str == "movement" {
+                let movement_name: String = value.as_string().ok_or_else(|| bad_json(String::from("movement is not a string")))?.iter().collect();
+                movement = Movement::from_name(&movement_name)
+                    .ok_or_else(|| bad_json(format!("unknown movement '{}', expected \"free\" or \"grid\"", movement_name)))?;
+            }
+            if key_str == "levels" {
+                let level_values = value.as_array().ok_or_else(|| bad_json(String::from("levels is not an array")))?;
+                for (i, level_value) in level_values.iter().enumerate() {
+                    let invalid = |what: &str| bad_json(format!("level {}: {}", i + 1, what));
+                    let level_obj = level_value.as_object().ok_or_else(|| invalid("expected an object"))?;
+
+                    let mut level = PackLevel::default();
+                    for (key, value) in level_obj.iter() {
+                        let key_str: String = key.iter().collect();
+                        let string = || -> Result<String, AssetError> {
+                            Ok(value.as_string().ok_or_else(|| invalid(&format!("{} is not a string", key_str)))?.iter().collect())
+                        };
+                        match key_str.as_str() {
+                            "level" => level.level = string()?,
+                            "name" => level.name = Some(string()?),
+                            "intro" => level.intro = Some(string()?),
+                            "tile_set" => level.tile_set = Some(string()?),
+                            "entities" => level.entities = Some(string()?),
+                            "par_moves" => {
+                                level.par_moves = Some(value.as_number().ok_or_else(|| invalid("par_moves is not a number"))?.integer as usize);
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    if level.level.is_empty() {
+                        return Err(invalid("level is missing"));
+                    }
+                    levels.push(level);
+                }
+            }
+        }
+
+        if levels.is_empty() {
+            return Err(bad_json(String::from("the pack has no levels")));
+        }
+
+        Ok(LevelPack {
+            name,
+            movement,
+            levels,
+        })
+    }
+
+    // The pack's entry for a level, levels that are not in the pack use the defaults.
+    pub fn level(&self, level: &str) -> PackLevel {
+        match self.levels.iter().find(|pack_level| pack_level.level == level) {
+            Some(pack_level) => pack_level.clone(),
+            None => PackLevel { level: String::from(level), ..PackLevel::default() },
+        }
+    }
+}
+
+impl Level {
+    // Size of levels in the old two file format, .level files give their own size.
+    pub const WIDTH: usize = 40;
+    pub const HEIGHT: usize = 30;
+
+    pub fn new_from_name(assets: &dyn AssetSource, entity_loader: &EntityLoader, level_name: &str) -> Result<Self, AssetError> {
+        // A single .level file is preferred over the two files of the old format.
+        let single_file_name = format!("{}.{}", level_name, level_file::EXTENSION);
+        match assets.read_file(&single_file_name, Some("levels")) {
+            Ok(bytes) => {
+                let file = AssetError::path(&single_file_name, Some("levels"));
+                let level_file = level_file::parse(&file, &bytes).map_err(|diagnostics| AssetError::BadLevel { diagnostics })?;
+                let mut level = Self::new_from_layers(entity_loader, level_file.layer("terrain").unwrap(), level_file.layer("items").unwrap())?;
+                level.player.inventory_size = level_file.inventory_size;
+                return Ok(level);
+            }
+            Err(AssetError::NotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+
+        let level_file_name = format!("{}.lvl", level_name);
+        let level_bytes = assets.read_file(&level_file_name, Some("levels"))?;
+
+        let level_items_file_name = format!("{}.lvl.items", level_name);
+        let level_items_bytes = assets.read_file(&level_items_file_name, Some("levels"))?;
+
+        let level_file = AssetError::path(&level_file_name, Some("levels"));
+        let items_file = AssetError::path(&level_items_file_name, Some("levels"));
+        Self::new_from_layers(entity_loader, &Layer::new_from_file(&level_file, &level_bytes), &Layer::new_from_file(&items_file, &level_items_bytes))
+    }
+
+    pub fn new_from_layers(entity_loader: &EntityLoader, terrain: &Layer, items: &Layer) -> Result<Self, AssetError> {
+        // Everything below indexes the layers blindly, so refuse broken levels up front.
+        let diagnostics = validate_layers(entity_loader, terrain, items);
+        if has_errors(&diagnostics) {
+            return Err(AssetError::BadLevel { diagnostics });
+        }
+
+        let terrain_rows: Vec<&[u8]> = terrain.bytes.split(|byte| *byte == b'\n').collect();
+        let items_rows: Vec<&[u8]> = items.bytes.split(|byte| *byte == b'\n').collect();
+
+        let mut entities = vec![];
+        for x in 0..terrain.width {
+            entities.push(vec![]);
+
+            for row in terrain_rows.iter().take(terrain.height) {
+                let entity_id_char: char = row[x].into();
+                let entity_id = format!("{}", entity_id_char);
+                let field_entity = entity_loader.get(&entity_id)?.clone();
+                entities[x].push(field_entity);
+            }
+        }
+
+        let mut player = Player::new(entity_loader)?;
+        let mut masks = vec![];
+        let mut treasure = Treasure::new(entity_loader)?;
+        let mut crates = vec![];
+        for x in 0..terrain.width {
+            for y in 0..terrain.height {
+                // Rows of a .level items layer may end early.
+                let item_id_char: char = items_rows.get(y).and_then(|row| row.get(x)).map_or(' ', |item| (*item).into());
+                let pos = Vec2::new((x * Tile::WIDTH) as i32, (y * Tile::HEIGHT) as i32);
+                match item_id_char {
+                    'T' => {
+                        treasure.sprite.pos = pos;
+                    }
+                    'P' => {
+                        player.sprite.pos = pos;
+                    }
+                    item => {
+                        if let Some(color) = entity_loader.mask_color_from_item(item) {
+                            let mut mask = Mask::new_from_color_id(color, entity_loader);
+                            mask.sprite.pos = pos;
+                            masks.push(mask);
+                        } else if let Some(entity) = entity_loader.item_entity(item).filter(|entity| entity.pushable) {
+                            let mut pushable = Crate::new(entity);
+                            pushable.sprite.pos = pos;
+                            crates.push(pushable);
+                        }
+                    }
+                }
+            }
+        }
+
+        let plates = entities.iter().flatten().filter(|entity| entity.plate).count();
+        Ok(Level {
+            sprite: Sprite::new(entities),
+            player,
+            masks,
+            treasure,
+            crates,
+            plates,
+            mixes: entity_loader.mixes.clone(),
+        })
+    }
+
+    // The walls and crates the sprite runs into, as seen with worn_mask.
+    pub fn collides(&self, sprite: &Sprite, move_dir: Vec2, worn_mask: Option<usize>) -> Option<Vec<Entity>> {
+        let moved_pos = sprite.pos + move_dir;
+        let index_pos = moved_pos - self.sprite.pos;
+
+        let (first_x, first_y) = (index_pos[0] / (Tile::WIDTH as i32), index_pos[1] / (Tile::HEIGHT as i32));
+        let (end_x, end_y) = (first_x + (sprite.tiles_width() as i32) + 1, first_y + (sprite.tiles_height() as i32) + 1);
+
+        // Only look at tiles inside the level, everything outside is empty space.
+        let (first_x, first_y) = (first_x.max(0), first_y.max(0));
+        let (end_x, end_y) = (end_x.min(self.sprite.tiles_width() as i32), end_y.min(self.sprite.tiles_height() as i32));
+
+        let mut collision_entities = vec![];
+        for x in first_x..end_x {
+            for y in first_y..end_y {
+                let entity = self.sprite.entities[x as usize][y as usize].seen_with(worn_mask).clone();
+                if entity.wall {
+                    let (s1_start_x, s1_start_y) = (self.sprite.pos[0] + x * (Tile::WIDTH as i32), self.sprite.pos[1] + y * (Tile::HEIGHT as i32));
+                    let (s1_end_x, s1_end_y) = (self.sprite.pos[0] + (x + 1) * (Tile::WIDTH as i32), self.sprite.pos[1] + (y + 1) * (Tile::HEIGHT as i32));
+
+                    let (s2_start_x, s2_start_y) = (moved_pos[0], moved_pos[1]);
+                    let (s2_end_x, s2_end_y) = (moved_pos[0] + sprite.width() as i32, moved_pos[1] + sprite.height() as i32);
+
+                    let collides = ((s1_start_x >= s2_start_x && s1_start_x < s2_end_x) || (s1_end_x > s2_start_x && s1_end_x <= s2_end_x))
+                        && ((s1_start_y >= s2_start_y && s1_start_y < s2_end_y) || (s1_end_y > s2_start_y && s1_end_y <= s2_end_y));
+
+                    if collides {
+                        collision_entities.push(entity);
+                    }
+                }
+            }
+        }
+        for index in self.crates_in_way(sprite, move_dir) {
+            collision_entities.push(self.crates[index].sprite.entities[0][0].clone());
+        }
+
+        if collision_entities.is_empty() {
+            None
+        } else {
+            Some(collision_entities)
+        }
+    }
+
+    pub fn move_player(&mut self, move_dir: Vec2) {
+        let masks = self.worn_masks();
+        let mut walls = self.collides(&self.player.sprite, move_dir, masks.worn).unwrap_or_default();
+        // Crates never open, they are pushed out of the way instead.
+        walls.retain(|entity| !entity.pushable);
+        if Self::can_walk_through(&walls, &masks) && self.push_crates(move_dir) {
+            self.player.sprite.pos += move_dir;
+        }
+    }
+
+    // What the doors look at, see WornMasks.
+    pub fn worn_masks(&self) -> WornMasks {
+        WornMasks { plates_pressed: self.plates_pressed(), ..self.player.worn_masks() }
+    }
+
+    // Whether there is a crate on every plate tile, a crate is on the tile below its middle. A
+    // level without plates has none to press.
+    pub fn plates_pressed(&self) -> bool {
+        let pressed = self.crates.iter().filter(|pushable| self.entity_under(&pushable.sprite).map_or(false, |entity| entity.plate)).count();
+        self.plates > 0 && pressed == self.plates
+    }
+
+    // The crates the sprite runs into when it moves by move_dir, other than itself.
+    fn crates_in_way(&self, sprite: &Sprite, move_dir: Vec2) -> Vec<usize> {
+        let moved = Rect::new(sprite.pos[0] + move_dir[0], sprite.pos[1] + move_dir[1], sprite.width(), sprite.height());
+        (0..self.crates.len())
+            .filter(|index| {
+                let other = &self.crates[*index].sprite;
+                !core::ptr::eq(other, sprite) && other.rect().intersection(&moved).is_some()
+            })
+            .collect()
+    }
+
+    /*
+     * Pushes the crates the player walks into a whole tile along, if all of them can move;
+     * false if they can't. Crates stay on the tiles like in grid movement, so plates are
+     * pressed by a crate standing on them and the solver sees the same level.
+     */
+    fn push_crates(&mut self, move_dir: Vec2) -> bool {
+        let pushed = self.crates_in_way(&self.player.sprite, move_dir);
+        let tile_move = Vec2::new(move_dir[0] * Tile::WIDTH as i32, move_dir[1] * Tile::HEIGHT as i32);
+        if !pushed.iter().all(|index| self.crate_can_move(*index, tile_move)) {
+            return false;
+        }
+        for index in pushed {
+            self.crates[index].sprite.pos += tile_move;
+        }
+        true
+    }
+
+    // A crate only moves inside the level, onto floor the player could walk on with its masks and not onto items.
+    fn crate_can_move(&self, index: usize, move_dir: Vec2) -> bool {
+        let sprite = &self.crates[index].sprite;
+        let moved = Rect::new(sprite.pos[0] + move_dir[0], sprite.pos[1] + move_dir[1], sprite.width(), sprite.height());
+        if self.sprite.rect().intersection(&moved) != Some(moved) {
+            return false;
+        }
+
+        let masks = self.worn_masks();
+        if let Some(entities) = self.collides(sprite, move_dir, masks.worn) {
+            if !Self::can_walk_through(&entities, &masks) {
+                return false;
+            }
+        }
+        let mut items = self.masks.iter().map(|mask| &mask.sprite).chain([&self.treasure.sprite]);
+        !items.any(|item| item.rect().intersection(&moved).is_some())
+    }
+
+    // Grid movement: the tile next to the player's own in direction move_dir, None outside of the level.
+    fn tile_next_to_player(&self, move_dir: Vec2) -> Option<(usize, usize)> {
+        let target = self.player.sprite.pos - self.sprite.pos + Vec2::new(move_dir[0] * Tile::WIDTH as i32, move_dir[1] * Tile::HEIGHT as i32);
+        let (x, y) = (target[0] / Tile::WIDTH as i32, target[1] / Tile::HEIGHT as i32);
+        if target[0] < 0 || target[1] < 0 || x >= self.sprite.tiles_width() as i32 || y >= self.sprite.tiles_height() as i32 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+
+    // Grid movement: whether the player can enter the tile next to its own in direction move_dir, pushing the crate on it.
+    pub fn can_step(&self, move_dir: Vec2) -> bool {
+        // There is nothing to walk on outside of the level.
+        let (x, y) = match self.tile_next_to_player(move_dir) {
+            Some(tile) => tile,
+            None => return false,
+        };
+
+        let masks = self.worn_masks();
+        let entity = &self.sprite.entities[x][y];
+        if !entity.seen_with(masks.worn).opens_for(&masks) {
+            return false;
+        }
+        let tile_move = Vec2::new(move_dir[0] * Tile::WIDTH as i32, move_dir[1] * Tile::HEIGHT as i32);
+        self.crate_at_step(move_dir).map_or(true, |index| self.crate_can_move(index, tile_move))
+    }
+
+    // Grid movement: the crate on the tile the player steps onto in direction move_dir, it is pushed along.
+    pub fn crate_at_step(&self, move_dir: Vec2) -> Option<usize> {
+        let (x, y) = self.tile_next_to_player(move_dir)?;
+        let tile = Rect::new(self.sprite.pos[0] + (x * Tile::WIDTH) as i32, self.sprite.pos[1] + (y * Tile::HEIGHT) as i32, Tile::WIDTH, Tile::HEIGHT);
+        self.crates.iter().position(|pushable| pushable.sprite.rect().intersection(&tile).is_some())
+    }
+
+    // Handle collision: the player can walk through the walls if all of them open for its masks.
+    pub fn can_walk_through(entities: &[Entity], masks: &WornMasks) -> bool {
+        entities.iter().all(|entity| entity.opens_for(masks))
+    }
+
+    /*
+     * SPACE: takes the mask the player stands on, swapping it for the worn one if the
+     * inventory is full. Without a mask to take the worn one is dropped. On a mixer tile
+     * the worn mask and the one lying there are mixed instead, if they can be.
+     */
+    pub fn swap_mask(&mut self, entity_loader: &EntityLoader) {
+        let mask_index = self.masks.iter().position(|mask| self.player.sprite.collides(&mask.sprite));
+        if let (Some(index), Some(worn_mask)) = (mask_index, self.player.worn_mask()) {
+            let on_mixer = self.entity_under(&self.player.sprite).map_or(false, |entity| entity.mixer);
+            if let Some(mix) = self.mix(worn_mask, self.masks[index].mask_color).filter(|_| on_mixer) {
+                self.masks.remove(index);
+                self.player.masks[self.player.worn] = mix;
+                return;
+            }
+        }
+
+        let dropped_mask = match mask_index {
+            Some(index) => {
+                let mask = self.masks.remove(index);
+                self.player.take_mask(mask.mask_color)
+            }
+            None => self.player.drop_mask(),
+        };
+
+        if let Some(color) = dropped_mask {
+            let mut mask = Mask::new_from_color_id(color, entity_loader);
+            mask.sprite.pos = self.player.sprite.pos;
+            self.masks.push(mask);
+        }
+    }
+
+    // The color two masks are mixed to, see MaskMix.
+    pub fn mix(&self, color: usize, other_color: usize) -> Option<usize> {
+        self.mixes.iter().find(|mix| mix.colors == [color, other_color] || mix.colors == [other_color, color]).map(|mix| mix.mix)
+    }
+
+    // The terrain entity below the middle of the sprite.
+    pub fn entity_under(&self, sprite: &Sprite) -> Option<&Entity> {
+        let center = sprite.pos - self.sprite.pos + Vec2::new(sprite.width() as i32 / 2, sprite.height() as i32 / 2);
+        if center[0] < 0 || center[1] < 0 {
+            return None;
+        }
+        let (x, y) = (center[0] as usize / Tile::WIDTH, center[1] as usize / Tile::HEIGHT);
+        self.sprite.entities.get(x).and_then(|column| column.get(y))
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.player.sprite.collides(&self.treasure.sprite)
+    }
+
+    // Draws the part of the level the camera sees, see Camera::new_following.
+    pub fn draw(&self, tile_set: &TileSet, vfb: &mut VirtualFrameBuffer) {
+        let camera = Camera::new_following(self, vfb.width(), vfb.height());
+        self.draw_with_camera(tile_set, vfb, &camera);
+    }
+
+    pub fn draw_with_camera(&self, tile_set: &TileSet, vfb: &mut VirtualFrameBuffer, camera: &Camera) {
+        self.draw_clipped(tile_set, vfb, camera, vfb.rect());
+    }
+
+    // Redraws only the part of the screen inside clip, what is below the sprites has to be cleared first.
+    pub fn draw_clipped(&self, tile_set: &TileSet, vfb: &mut VirtualFrameBuffer, camera: &Camera, clip: Rect) {
+        let terrain = RevealedSprite { sprite: &self.sprite, worn_mask: self.player.worn_mask() };
+        terrain.draw_clipped(tile_set, vfb, camera.pos, clip);
+
+        for mask in self.masks.iter() {
+            mask.sprite.draw_clipped(tile_set, vfb, camera.pos, clip);
+        }
+        for pushable in self.crates.iter() {
+            pushable.sprite.draw_clipped(tile_set, vfb, camera.pos, clip);
+        }
+
+        self.treasure.sprite.draw_clipped(tile_set, vfb, camera.pos, clip);
+        self.player.sprite.draw_clipped(tile_set, vfb, camera.pos, clip);
+    }
+
+    // Where the masks, the crates, the treasure and the player are on the screen and what they look like.
+    pub fn drawn_sprites(&self, camera: &Camera) -> Vec<DrawnSprite> {
+        let crates = self.crates.iter().map(|pushable| &pushable.sprite);
+        let sprites = self.masks.iter().map(|mask| &mask.sprite).chain(crates).chain([&self.treasure.sprite, &self.player.sprite]);
+        sprites.map(|sprite| DrawnSprite::new(sprite, camera)).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrawnSprite {
+    pub rect: Rect,
+    pub tiles: Vec<(u8, u8)>,
+}
+
+impl DrawnSprite {
+    pub fn new(sprite: &Sprite, camera: &Camera) -> Self {
+        let pos = sprite.pos - camera.pos;
+        DrawnSprite {
+            rect: Rect::new(pos[0], pos[1], sprite.width(), sprite.height()),
+            tiles: sprite.entities.iter().flatten().map(|entity| (entity.tile_x, entity.tile_y)).collect(),
+        }
+    }
+}
+
+/*
+ * The window of the level that is shown on the screen, pos is the level position shown
+ * in the top left corner. It follows the player and stops at the edges of the level; a
+ * level smaller than the screen is shown in the middle of it instead.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub pos: Vec2,
+}
+
+impl Camera {
+    pub fn new_following(level: &Level, screen_width: usize, screen_height: usize) -> Self {
+        let player = &level.player.sprite;
+        let center = player.pos + Vec2::new(player.width() as i32 / 2, player.height() as i32 / 2);
+
+        let axis = |center: i32, level_start: i32, level_size: usize, screen_size: usize| {
+            let (level_size, screen_size) = (level_size as i32, screen_size as i32);
+            if level_size <= screen_size {
+                level_start - (screen_size - level_size) / 2
+            } else {
+                (center - screen_size / 2).clamp(level_start, level_start + level_size - screen_size)
+            }
+        };
+
+        Camera {
+            pos: Vec2::new(
+                axis(center[0], level.sprite.pos[0], level.sprite.width(), screen_width),
+                axis(center[1], level.sprite.pos[1], level.sprite.height(), screen_height),
+            ),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::graphics::{Level, Player};
+use crate::validate::{Diagnostic, Severity};
+
+/*
+ * A level in a single file, <name>.level in the levels directory:
+ *
+ *   MaskOS level
+ *   width: 40
+ *   height: 30
+ *   inventory: 2
+ *   name: The crossroads
+ *
+ *   [terrain]
+ *   ABBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBC
+ *   ...
+ *   [items]
+ *
+ *                P   R
+ *   ...
+ *
+ * The header starts with the "MaskOS level" line, followed by "key: value" lines.
+ * width and height are required and give the size of every layer in tiles, levels can
+ * be larger than the screen. inventory is how many masks the player can carry at once,
+ * one if it is left out. Every other key is kept as metadata. Empty header
+ * lines and lines starting with '#' are skipped. Every "[name]" line starts a layer with
+ * one row per line, the same grids as in <name>.lvl and <name>.lvl.items. Rows of
+ * every layer but the terrain may be shorter or missing, the rest is empty.
+ */
+
+pub const EXTENSION: &str = "level";
+pub const MAGIC: &str = "MaskOS level";
+pub const LAYERS: [&str; 2] = ["terrain", "items"];
+
+// One grid of a level, where it is in which file for the diagnostics.
+#[derive(Clone, Copy, Debug)]
+pub struct Layer<'a> {
+    pub file: &'a str,
+    // Line number of the first row in the file
+    pub first_line: usize,
+    pub bytes: &'a [u8],
+    pub short_rows: bool,
+    // Size of the grid in tiles
+    pub width: usize,
+    pub height: usize,
+}
+
+impl<'a> Layer<'a> {
+    // A grid that is a file of its own, like <name>.lvl. These are always Level::WIDTH x Level::HEIGHT.
+    pub fn new_from_file(file: &'a str, bytes: &'a [u8]) -> Self {
+        Layer { file, first_line: 1, bytes, short_rows: false, width: Level::WIDTH, height: Level::HEIGHT }
+    }
+}
+
+pub struct LevelFile<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub inventory_size: usize,
+    pub metadata: Vec<(String, String)>,
+    pub layers: Vec<(String, Layer<'a>)>,
+    // What parse found wrong with the header that doesn't keep the level from loading.
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl<'a> LevelFile<'a> {
+    pub fn layer(&self, name: &str) -> Option<&Layer<'a>> {
+        self.layers.iter().find(|(layer_name, _)| layer_name == name).map(|(_, layer)| layer)
+    }
+
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.iter().find(|(metadata_key, _)| metadata_key == key).map(|(_, value)| value.as_str())
+    }
+}
+
+fn error(file: &str, line: usize, message: String) -> Diagnostic {
+    Diagnostic { severity: Severity::Error, file: file.to_string(), line, column: if line == 0 { 0 } else { 1 }, message }
+}
+
+// Splits the file into its header and layers, the layers themselves are checked by validate.
+pub fn parse<'a>(file: &'a str, bytes: &'a [u8]) -> Result<LevelFile<'a>, Vec<Diagnostic>> {
+    let mut diagnostics = vec![];
+    let mut width = None;
+    let mut height = None;
+    let mut inventory_size = Player::DEFAULT_INVENTORY_SIZE;
+    let mut metadata = vec![];
+    let mut layers: Vec<(String, Layer)> = vec![];
+    let mut seen_magic = false;
+
+    // The layer being read: name, line of its first row and where its rows start.
+    let mut current: Option<(String, usize, usize)> = None;
+    let mut offset = 0;
+    for (i, line) in bytes.split(|byte| *byte == b'\n').enumerate() {
+        let line_number = i + 1;
+        let start = offset;
+        offset = (offset + line.len() + 1).min(bytes.len());
+
+        let text = core::str::from_utf8(line).unwrap_or("");
+        let text = text.strip_suffix('\r').unwrap_or(text);
+
+        if text.starts_with('[') && text.ends_with(']') {
+            let name = &text[1..text.len() - 1];
+            if let Some((name, first_line, layer_start)) = current.take() {
+                let short_rows = name != "terrain";
+                layers.push((name, Layer { file, first_line, bytes: &bytes[layer_start..start], short_rows, width: 0, height: 0 }));
+            }
+            if layers.iter().any(|(layer_name, _)| layer_name == name) {
+                diagnostics.push(error(file, line_number, format!("layer [{}] is defined twice", name)));
+            }
+            if !LAYERS.contains(&name) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    file: file.to_string(),
+                    line: line_number,
+                    column: 2,
+                    message: format!("unknown layer [{}] is ignored", name),
+                });
+            }
+            current = Some((name.to_string(), line_number + 1, offset));
+            continue;
+        }
+
+        if current.is_some() {
+            continue;
+        }
+
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if !seen_magic {
+            if text != MAGIC {
+                diagnostics.push(error(file, line_number, format!("expected {:?} as the first line", MAGIC)));
+                return Err(diagnostics);
+            }
+            seen_magic = true;
+            continue;
+        }
+
+        match text.split_once(':') {
+            Some((key, value)) => {
+                let (key, value) = (key.trim(), value.trim());
+                match key {
+                    "width" | "height" => match value.parse::<usize>() {
+                        Ok(number) if number > 0 => {
+                            if key == "width" {
+                                width = Some(number);
+                            } else {
+                                height = Some(number);
+                            }
+                        }
+                        _ => diagnostics.push(error(file, line_number, format!("{} has to be a positive number", key))),
+                    },
+                    "inventory" => match value.parse::<usize>() {
+                        Ok(number) if number > 0 => inventory_size = number,
+                        _ => diagnostics.push(error(file, line_number, String::from("inventory has to be a positive number"))),
+                    },
+                    _ => metadata.push((key.to_string(), value.to_string())),
+                }
+            }
+            None => diagnostics.push(error(file, line_number, String::from("expected \"key: value\" in the header"))),
+        }
+    }
+
+    if let Some((name, first_line, layer_start)) = current.take() {
+        let short_rows = name != "terrain";
+        layers.push((name, Layer { file, first_line, bytes: &bytes[layer_start..], short_rows, width: 0, height: 0 }));
+    }
+
+    if !seen_magic {
+        diagnostics.push(error(file, 0, format!("expected {:?} as the first line", MAGIC)));
+    }
+    for (key, value) in [("width", width), ("height", height)] {
+        if value.is_none() {
+            diagnostics.push(error(file, 0, format!("the header has no {}", key)));
+        }
+    }
+    for name in LAYERS {
+        if !layers.iter().any(|(layer_name, _)| layer_name == name) {
+            diagnostics.push(error(file, 0, format!("layer [{}] is missing", name)));
+        }
+    }
+
+    if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+        return Err(diagnostics);
+    }
+
+    let (width, height) = (width.unwrap(), height.unwrap());
+    for (_, layer) in layers.iter_mut() {
+        layer.width = width;
+        layer.height = height;
+    }
+
+    Ok(LevelFile {
+        width,
+        height,
+        inventory_size,
+        metadata,
+        layers,
+        warnings: diagnostics,
+    })
+}
+
+// Writes the two grids of the old format as one .level file.
+pub fn convert(level_bytes: &[u8], items_bytes: &[u8], metadata: &[(String, String)]) -> String {
+    let mut text = format!("{}\nwidth: {}\nheight: {}\n", MAGIC, Level::WIDTH, Level::HEIGHT);
+    for (key, value) in metadata {
+        text += &format!("{}: {}\n", key, value);
+    }
+
+    for (name, bytes) in [("terrain", level_bytes), ("items", items_bytes)] {
+        text += &format!("\n[{}]\n", name);
+
+        let mut rows: Vec<String> = String::from_utf8_lossy(bytes).lines().take(Level::HEIGHT).map(|row| row.to_string()).collect();
+        if name != "terrain" {
+            // Empty cells at the end of a row and empty rows at the end can go.
+            for row in rows.iter_mut() {
+                *row = row.trim_end_matches(|c| c == ' ' || c == '.').to_string();
+            }
+            while rows.last().map_or(false, |row| row.is_empty()) {
+                rows.pop();
+            }
+        }
+
+        for row in rows {
+            text += &row;
+            text.push('\n');
+        }
+    }
+    text
+}
@@ -0,0 +1,15 @@
+#![no_std]
+
+#[macro_use]
+extern crate alloc;
+
+pub mod math;
+pub mod graphics;
+pub mod platform;
+pub mod game;
+pub mod demo;
+pub mod solver;
+pub mod validate;
+pub mod error;
+pub mod level_file;
+pub mod screen;
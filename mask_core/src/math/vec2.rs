@@ -49,7 +49,7 @@ impl Add for Vec2 {
 }
 
 impl AddAssign for Vec2 {
-    fn add_assign(&mut self, other: Vec2) -> () {
+    fn add_assign(&mut self, other: Vec2) {
         *self = Vec2 {
             e: [self[0] + other[0], self[1] + other[1]]
         };
@@ -67,7 +67,7 @@ impl Sub for Vec2 {
 }
 
 impl SubAssign for Vec2 {
-    fn sub_assign(&mut self, other: Vec2) -> () {
+    fn sub_assign(&mut self, other: Vec2) {
         *self = Vec2 {
             e: [self[0] - other[0], self[1] - other[1]]
         };
@@ -85,7 +85,7 @@ impl Mul<i32> for Vec2 {
 }
 
 impl MulAssign<i32> for Vec2 {
-    fn mul_assign(&mut self, other: i32) -> () {
+    fn mul_assign(&mut self, other: i32) {
         *self = Vec2 {
             e: [self[0] * other, self[1] * other]
         };
@@ -103,7 +103,7 @@ impl Mul<Vec2> for Vec2 {
 }
 
 impl MulAssign<Vec2> for Vec2 {
-    fn mul_assign(&mut self, other: Vec2) -> () {
+    fn mul_assign(&mut self, other: Vec2) {
         *self = Vec2 {
             e: [self[0] * other[0], self[1] * other[1]]
         };
@@ -131,7 +131,7 @@ impl Div<i32> for Vec2 {
 }
 
 impl DivAssign<i32> for Vec2 {
-    fn div_assign(&mut self, other: i32) -> () {
+    fn div_assign(&mut self, other: i32) {
         *self = Vec2 {
             e: [self[0] / other, self[1] / other]
         };
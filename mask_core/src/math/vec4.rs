@@ -58,7 +58,7 @@ impl Add for Vec4 {
 }
 
 impl AddAssign for Vec4 {
-    fn add_assign(&mut self, other: Vec4) -> () {
+    fn add_assign(&mut self, other: Vec4) {
         *self = Vec4 {
             e: [self[0] + other[0], self[1] + other[1], self[2] + other[2], self[3] + other[3]]
         };
@@ -76,7 +76,7 @@ impl Sub for Vec4 {
 }
 
 impl SubAssign for Vec4 {
-    fn sub_assign(&mut self, other: Vec4) -> () {
+    fn sub_assign(&mut self, other: Vec4) {
         *self = Vec4 {
             e: [self[0] - other[0], self[1] - other[1], self[2] - other[2], self[3] - other[3]]
         };
@@ -94,7 +94,7 @@ impl Mul<i32> for Vec4 {
 }
 
 impl MulAssign<i32> for Vec4 {
-    fn mul_assign(&mut self, other: i32) -> () {
+    fn mul_assign(&mut self, other: i32) {
         *self = Vec4 {
             e: [self[0] * other, self[1] * other, self[2] * other, self[3] * other]
         };
@@ -112,7 +112,7 @@ impl Mul<Vec4> for Vec4 {
 }
 
 impl MulAssign<Vec4> for Vec4 {
-    fn mul_assign(&mut self, other: Vec4) -> () {
+    fn mul_assign(&mut self, other: Vec4) {
         *self = Vec4 {
             e: [self[0] * other[0], self[1] * other[1], self[2] * other[2], self[3] * other[3]]
         };
@@ -140,7 +140,7 @@ impl Div<i32> for Vec4 {
 }
 
 impl DivAssign<i32> for Vec4 {
-    fn div_assign(&mut self, other: i32) -> () {
+    fn div_assign(&mut self, other: i32) {
         *self = Vec4 {
             e: [self[0] / other, self[1] / other, self[3] / other, self[3] / other]
         };
@@ -0,0 +1,66 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::error::AssetError;
+use crate::graphics::VirtualFrameBuffer;
+
+/*
+ * Everything the game needs from the machine it runs on. The UEFI binary implements
+ * these on top of the boot services, other backends can read from disk or memory.
+ */
+
+pub trait AssetSource {
+    fn read_file(&self, file_name: &str, directory: Option<&str>) -> Result<Vec<u8>, AssetError>;
+
+    // The names of the files in a directory, in no particular order.
+    fn list_directory(&self, directory: &str) -> Result<Vec<String>, AssetError>;
+}
+
+// A monotonic clock, game::StepTimer turns it into fixed steps of the game logic.
+pub trait Clock {
+    fn now_micros(&self) -> u64;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameKey {
+    Left,
+    Right,
+    Up,
+    Down,
+    Action,
+    // Puts on the next carried mask
+    Cycle,
+}
+
+impl GameKey {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameKey::Left => "left",
+            GameKey::Right => "right",
+            GameKey::Up => "up",
+            GameKey::Down => "down",
+            GameKey::Action => "space",
+            GameKey::Cycle => "tab",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(GameKey::Left),
+            "right" => Some(GameKey::Right),
+            "up" => Some(GameKey::Up),
+            "down" => Some(GameKey::Down),
+            "space" => Some(GameKey::Action),
+            "tab" => Some(GameKey::Cycle),
+            _ => None,
+        }
+    }
+}
+
+pub trait Input {
+    fn read_key(&mut self) -> Option<GameKey>;
+}
+
+pub trait Display {
+    fn present(&mut self, vfb: &VirtualFrameBuffer);
+}
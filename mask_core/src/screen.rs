@@ -0,0 +1,88 @@
+use crate::math::Color4;
+
+/*
+ * The game always renders a logical VirtualFrameBuffer::WIDTH x HEIGHT scene. On a larger
+ * screen every logical pixel becomes a scale x scale block of screen pixels and the scene
+ * is centered, with black bars around it where the screen doesn't divide evenly.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Letterbox {
+    pub screen_width: usize,
+    pub screen_height: usize,
+    pub scale: usize,
+    // Screen position of the top left logical pixel
+    pub offset_x: usize,
+    pub offset_y: usize,
+}
+
+impl Letterbox {
+    // None if the screen is smaller than the scene.
+    pub fn new(screen_width: usize, screen_height: usize, width: usize, height: usize) -> Option<Self> {
+        let scale = (screen_width / width).min(screen_height / height);
+        if scale == 0 {
+            return None;
+        }
+
+        Some(Letterbox {
+            screen_width,
+            screen_height,
+            scale,
+            offset_x: (screen_width - width * scale) / 2,
+            offset_y: (screen_height - height * scale) / 2,
+        })
+    }
+
+    /*
+     * The index of the resolution that shows the scene best: the largest scale wins, then
+     * the one with the least screen left for the bars. None if every resolution is too small.
+     */
+    pub fn best_mode(resolutions: &[(usize, usize)], width: usize, height: usize) -> Option<usize> {
+        resolutions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (screen_width, screen_height))| {
+                let letterbox = Self::new(*screen_width, *screen_height, width, height)?;
+                Some((i, letterbox.scale, screen_width * screen_height))
+            })
+            .max_by(|(_, scale_a, area_a), (_, scale_b, area_b)| scale_a.cmp(scale_b).then(area_b.cmp(area_a)))
+            .map(|(i, _, _)| i)
+    }
+}
+
+/*
+ * Where the color channels are in a 32 bit framebuffer pixel, like the PixelBitmask of a
+ * GOP mode. Channels with fewer than 8 bits keep their most significant bits.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelMasks {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+}
+
+impl PixelMasks {
+    // Byte 0 is red, PixelFormat::Rgb
+    pub const RGB: PixelMasks = PixelMasks { red: 0x0000ff, green: 0x00ff00, blue: 0xff0000 };
+    // Byte 0 is blue, PixelFormat::Bgr
+    pub const BGR: PixelMasks = PixelMasks { red: 0xff0000, green: 0x00ff00, blue: 0x0000ff };
+
+    pub fn pack(&self, color: Color4) -> u32 {
+        Self::channel(color[0] as u32, self.red) | Self::channel(color[1] as u32, self.green) | Self::channel(color[2] as u32, self.blue)
+    }
+
+    // A pixel of the VirtualFrameBuffer in this layout.
+    pub fn repack(&self, pixel: u32) -> u32 {
+        Self::channel(pixel >> 16, self.red) | Self::channel(pixel >> 8, self.green) | Self::channel(pixel, self.blue)
+    }
+
+    fn channel(value: u32, mask: u32) -> u32 {
+        if mask == 0 {
+            return 0;
+        }
+        let shift = mask.trailing_zeros();
+        let bits = (mask >> shift).count_ones();
+        // The 8 bits of the value go to the top of the channel, wider channels aren't dimmed.
+        let value = if bits < 8 { (value & 0xff) >> (8 - bits) } else { (value & 0xff) << (bits - 8) };
+        (value << shift) & mask
+    }
+}
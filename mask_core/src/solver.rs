@@ -0,0 +1,497 @@
+use alloc::collections::{BinaryHeap, VecDeque};
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use hashbrown::HashMap;
+
+use crate::demo::Demo;
+use crate::game::Game;
+use crate::graphics::{Entity, Level, Movement, Sprite, Tile, WornMasks};
+use crate::math::Vec2;
+use crate::platform::GameKey;
+
+/*
+ * Finds the shortest sequence of moves (one tile per arrow key, SPACE to take, drop or
+ * mix a mask, TAB to put on the next one) that brings the player from its start tile to the
+ * treasure, using the rules of Level::collides, Level::can_walk_through, Level::swap_mask
+ * and Player::cycle_mask.
+ *
+ * Masks never block the player, so between two SPACE or TAB presses the player just walks
+ * a shortest path for the masks it has on, see WornMasks. The search therefore only looks at the moments
+ * they are pressed: (player tile, carried masks, mask positions), and only on key tiles:
+ * the start tile, tiles with a mask, mixer tiles and floor next to tiles whose passability
+ * depends on the masks. Dropping a mask anywhere else does not change which doors can be
+ * reached, so "unsolvable" is exact; the move count is the shortest among solutions
+ * that only press SPACE and TAB on key tiles.
+ *
+ * Crates do block the player and every step can push one, so levels with crates are
+ * searched one key at a time instead, with the crate tiles in the state.
+ */
+
+const DIRECTIONS: [GameKey; 4] = [GameKey::Left, GameKey::Right, GameKey::Up, GameKey::Down];
+const UNREACHABLE: usize = usize::MAX;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct State {
+    player: usize,
+    // The carried masks starting with the worn one, in the order TAB puts them on.
+    carried: Vec<usize>,
+    // (tile, color), sorted
+    masks: Vec<(usize, usize)>,
+    // Tiles of the crates, sorted
+    crates: Vec<usize>,
+}
+
+impl State {
+    fn worn_mask(&self) -> Option<usize> {
+        self.carried.first().copied()
+    }
+
+    fn worn_masks(&self) -> WornMasks {
+        let mut carried = self.carried.clone();
+        carried.sort_unstable();
+        carried.dedup();
+        WornMasks { worn: self.worn_mask(), carried, plates_pressed: false }
+    }
+}
+
+struct Paths {
+    dist: Vec<usize>,
+    entered_by: Vec<Option<GameKey>>,
+}
+
+struct Grid {
+    width: usize,
+    height: usize,
+    treasure: usize,
+    mixers: Vec<bool>,
+    plates: Vec<usize>,
+    worn_masks: Vec<WornMasks>,
+    worn_masks_indices: HashMap<WornMasks, usize>,
+    // can_step[worn masks index][tile][direction index]
+    can_step: Vec<Vec<[bool; 4]>>,
+}
+
+fn direction(key: GameKey) -> Vec2 {
+    match key {
+        GameKey::Left => Vec2::new(-1, 0),
+        GameKey::Right => Vec2::new(1, 0),
+        GameKey::Up => Vec2::new(0, -1),
+        GameKey::Down => Vec2::new(0, 1),
+        GameKey::Action | GameKey::Cycle => Vec2::new(0, 0),
+    }
+}
+
+fn opposite(key: GameKey) -> GameKey {
+    match key {
+        GameKey::Left => GameKey::Right,
+        GameKey::Right => GameKey::Left,
+        GameKey::Up => GameKey::Down,
+        GameKey::Down => GameKey::Up,
+        GameKey::Action => GameKey::Action,
+        GameKey::Cycle => GameKey::Cycle,
+    }
+}
+
+fn tile_of(level: &Level, pos: Vec2) -> (i32, i32) {
+    let index_pos = pos - level.sprite.pos;
+    (index_pos[0] / Tile::WIDTH as i32, index_pos[1] / Tile::HEIGHT as i32)
+}
+
+impl Grid {
+    fn new(level: &Level) -> Self {
+        let (width, height) = (level.sprite.tiles_width(), level.sprite.tiles_height());
+        let (treasure_x, treasure_y) = tile_of(level, level.treasure.sprite.pos);
+
+        let mut mixers = vec![];
+        let mut plates = vec![];
+        for y in 0..height {
+            for x in 0..width {
+                let entity = &level.sprite.entities[x][y];
+                mixers.push(entity.mixer && !entity.wall);
+                if entity.plate {
+                    plates.push(y * width + x);
+                }
+            }
+        }
+
+        // Mixes are only looked at if there is somewhere to make them.
+        let mixes = level.mixes.iter().map(|mix| mix.mix).filter(|_| mixers.contains(&true));
+        let mut colors: Vec<usize> = level.masks.iter().map(|mask| mask.mask_color).chain(level.player.masks.iter().copied()).chain(mixes).collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        // Everything the player can have on: up to inventory_size of the colors, wearing one of
+        // them. Without plates they are never pressed.
+        let plates_pressed: &[bool] = if plates.is_empty() { &[false] } else { &[false, true] };
+        let mut worn_masks = vec![];
+        for subset in 0..1usize << colors.len() {
+            if subset.count_ones() as usize > level.player.inventory_size {
+                continue;
+            }
+            let carried: Vec<usize> = colors.iter().enumerate().filter(|(i, _)| subset & 1 << i != 0).map(|(_, color)| *color).collect();
+            let worn: Vec<Option<usize>> = if carried.is_empty() { vec![None] } else { carried.iter().copied().map(Some).collect() };
+            for worn in worn {
+                for pressed in plates_pressed {
+                    worn_masks.push(WornMasks { worn, carried: carried.clone(), plates_pressed: *pressed });
+                }
+            }
+        }
+        let worn_masks_indices = worn_masks.iter().enumerate().map(|(i, masks)| (masks.clone(), i)).collect();
+
+        // A tile sized probe, moved by one pixel, overlaps exactly the tile it starts on and
+        // the next one, just like the player does while walking from one tile to the next.
+        let mut probe = Sprite::new(vec![vec![Entity::default()]]);
+        let mut can_step = vec![];
+        for masks in worn_masks.iter() {
+            let mut steps = vec![];
+            for y in 0..height {
+                for x in 0..width {
+                    probe.pos = level.sprite.pos + Vec2::new((x * Tile::WIDTH) as i32, (y * Tile::HEIGHT) as i32);
+                    let mut tile_steps = [false; 4];
+                    for (i, key) in DIRECTIONS.iter().enumerate() {
+                        let dir = direction(*key);
+                        let (target_x, target_y) = (x as i32 + dir[0], y as i32 + dir[1]);
+                        if target_x < 0 || target_y < 0 || target_x >= width as i32 || target_y >= height as i32 {
+                            continue;
+                        }
+                        // Crates are not part of the terrain, the search moves them itself.
+                        tile_steps[i] = match level.collides(&probe, dir, masks.worn) {
+                            Some(mut entities) => {
+                                entities.retain(|entity| !entity.pushable);
+                                Level::can_walk_through(&entities, masks)
+                            }
+                            None => true,
+                        };
+                    }
+                    steps.push(tile_steps);
+                }
+            }
+            can_step.push(steps);
+        }
+
+        Grid {
+            width,
+            height,
+            treasure: treasure_y as usize * width + treasure_x as usize,
+            mixers,
+            plates,
+            worn_masks,
+            worn_masks_indices,
+            can_step,
+        }
+    }
+
+    fn neighbour(&self, tile: usize, key: GameKey) -> usize {
+        let dir = direction(key);
+        ((tile / self.width) as i32 + dir[1]) as usize * self.width + ((tile % self.width) as i32 + dir[0]) as usize
+    }
+
+    fn worn_masks_index(&self, state: &State) -> usize {
+        let plates_pressed = !self.plates.is_empty() && self.plates.iter().all(|plate| state.crates.contains(plate));
+        self.worn_masks_indices[&WornMasks { plates_pressed, ..state.worn_masks() }]
+    }
+
+    fn paths_from(&self, start: usize, worn_masks_index: usize) -> Paths {
+        let mut paths = Paths {
+            dist: vec![UNREACHABLE; self.width * self.height],
+            entered_by: vec![None; self.width * self.height],
+        };
+        paths.dist[start] = 0;
+
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(tile) = queue.pop_front() {
+            // Walking onto the treasure ends the level.
+            if tile == self.treasure {
+                continue;
+            }
+
+            for (i, key) in DIRECTIONS.iter().enumerate() {
+                if !self.can_step[worn_masks_index][tile][i] {
+                    continue;
+                }
+                let next = self.neighbour(tile, *key);
+                if paths.dist[next] == UNREACHABLE {
+                    paths.dist[next] = paths.dist[tile] + 1;
+                    paths.entered_by[next] = Some(*key);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        paths
+    }
+
+    fn key_tiles(&self, level: &Level, start: usize, masks: &[(usize, usize)]) -> Vec<usize> {
+        let mut key_tiles = vec![start];
+        for tile in masks.iter().map(|(tile, _)| *tile).chain((0..self.width * self.height).filter(|tile| self.mixers[*tile])) {
+            if !key_tiles.contains(&tile) {
+                key_tiles.push(tile);
+            }
+        }
+
+        for tile in 0..self.width * self.height {
+            for (i, key) in DIRECTIONS.iter().enumerate() {
+                let depends_on_mask = (1..self.worn_masks.len()).any(|m| self.can_step[m][tile][i] != self.can_step[0][tile][i]);
+                if !depends_on_mask {
+                    continue;
+                }
+
+                // The step from this tile depends on the masks, so they might need to be
+                // changed right here or right behind the door.
+                for candidate in [tile, self.neighbour(tile, *key)] {
+                    let entity = &level.sprite.entities[candidate % self.width][candidate / self.width];
+                    if entity.is_floor() && !key_tiles.contains(&candidate) {
+                        key_tiles.push(candidate);
+                    }
+                }
+            }
+        }
+
+        key_tiles.retain(|tile| *tile != self.treasure);
+        key_tiles
+    }
+}
+
+fn walk(paths: &Paths, grid: &Grid, from: usize, to: usize, moves: &mut Vec<GameKey>) {
+    let mut steps = vec![];
+    let mut tile = to;
+    while tile != from {
+        let key = paths.entered_by[tile].unwrap();
+        steps.push(key);
+        tile = grid.neighbour(tile, opposite(key));
+    }
+    moves.extend(steps.iter().rev());
+}
+
+// What SPACE (see Level::swap_mask) and TAB can change standing on tile.
+fn next_states(state: &State, tile: usize, level: &Level, grid: &Grid) -> Vec<(GameKey, State)> {
+    let mut next_states = vec![];
+
+    let mut masks = state.masks.clone();
+    let mut carried = state.carried.clone();
+    let mask_index = masks.iter().position(|(mask_tile, _)| *mask_tile == tile);
+    let mix = match (mask_index, state.worn_mask()) {
+        (Some(index), Some(worn_mask)) if grid.mixers[tile] => level.mix(worn_mask, masks[index].1),
+        _ => None,
+    };
+    match (mask_index, mix) {
+        (Some(index), Some(mix)) => {
+            masks.remove(index);
+            carried[0] = mix;
+            next_states.push((GameKey::Action, State { player: tile, carried, masks, crates: state.crates.clone() }));
+        }
+        (Some(index), None) => {
+            let (_, color) = masks.remove(index);
+            if carried.len() < level.player.inventory_size {
+                carried.insert(0, color);
+            } else {
+                masks.push((tile, core::mem::replace(&mut carried[0], color)));
+                masks.sort();
+            }
+            next_states.push((GameKey::Action, State { player: tile, carried, masks, crates: state.crates.clone() }));
+        }
+        (None, _) if !carried.is_empty() => {
+            masks.push((tile, carried.remove(0)));
+            masks.sort();
+            next_states.push((GameKey::Action, State { player: tile, carried, masks, crates: state.crates.clone() }));
+        }
+        (None, _) => {}
+    }
+
+    if state.carried.len() > 1 {
+        let mut carried = state.carried.clone();
+        carried.rotate_left(1);
+        next_states.push((GameKey::Cycle, State { player: tile, carried, masks: state.masks.clone(), crates: state.crates.clone() }));
+    }
+
+    next_states
+}
+
+/// Returns the shortest move sequence to the treasure, or `None` if the level cannot be solved.
+pub fn solve(level: &Level) -> Option<Vec<GameKey>> {
+    let grid = Grid::new(level);
+
+    let tile_index = |pos: Vec2| {
+        let (x, y) = tile_of(level, pos);
+        y as usize * grid.width + x as usize
+    };
+    let start_tile = tile_index(level.player.sprite.pos);
+    if start_tile == grid.treasure {
+        return Some(vec![]);
+    }
+
+    let mut masks: Vec<(usize, usize)> = level.masks.iter().map(|mask| (tile_index(mask.sprite.pos), mask.mask_color)).collect();
+    masks.sort();
+
+    let player = &level.player;
+    let carried = player.masks[player.worn..].iter().chain(&player.masks[..player.worn]).copied().collect();
+    let mut crates: Vec<usize> = level.crates.iter().map(|pushable| tile_index(pushable.sprite.pos)).collect();
+    crates.sort_unstable();
+    let start = State { player: start_tile, carried, masks, crates };
+    if !start.crates.is_empty() {
+        return solve_with_crates(level, &grid, start);
+    }
+
+    let key_tiles = grid.key_tiles(level, start_tile, &start.masks);
+    let mut paths = HashMap::new();
+    for tile in key_tiles.iter() {
+        for worn_masks_index in 0..grid.worn_masks.len() {
+            paths.insert((*tile, worn_masks_index), grid.paths_from(*tile, worn_masks_index));
+        }
+    }
+    let mut states = vec![start.clone()];
+    let mut state_ids = HashMap::new();
+    state_ids.insert(start, 0);
+    let mut costs = vec![0];
+    let mut parents: Vec<Option<usize>> = vec![None];
+    // The key pressed to get to a state
+    let mut keys = vec![GameKey::Action];
+    let mut best_win: Option<(usize, usize)> = None;
+
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((0, 0)));
+    while let Some(Reverse((cost, id))) = queue.pop() {
+        if cost > costs[id] {
+            continue;
+        }
+        if let Some((win_cost, _)) = best_win {
+            if win_cost <= cost {
+                break;
+            }
+        }
+
+        let state = states[id].clone();
+        let state_paths = &paths[&(state.player, grid.worn_masks_index(&state))];
+
+        let treasure_dist = state_paths.dist[grid.treasure];
+        if treasure_dist != UNREACHABLE && best_win.map_or(true, |(win_cost, _)| cost + treasure_dist < win_cost) {
+            best_win = Some((cost + treasure_dist, id));
+        }
+
+        for tile in key_tiles.iter() {
+            let dist = state_paths.dist[*tile];
+            if dist == UNREACHABLE {
+                continue;
+            }
+
+            for (key, next) in next_states(&state, *tile, level, &grid) {
+                let next_cost = cost + dist + 1;
+                let next_id = *state_ids.entry(next.clone()).or_insert_with(|| {
+                    states.push(next);
+                    costs.push(UNREACHABLE);
+                    parents.push(None);
+                    keys.push(key);
+                    states.len() - 1
+                });
+                if next_cost < costs[next_id] {
+                    costs[next_id] = next_cost;
+                    parents[next_id] = Some(id);
+                    keys[next_id] = key;
+                    queue.push(Reverse((next_cost, next_id)));
+                }
+            }
+        }
+    }
+
+    let (_, last_id) = best_win?;
+    let mut chain = vec![last_id];
+    while let Some(parent) = parents[*chain.last().unwrap()] {
+        chain.push(parent);
+    }
+    chain.reverse();
+
+    let mut moves = vec![];
+    for pair in chain.windows(2) {
+        let (from, to) = (&states[pair[0]], &states[pair[1]]);
+        walk(&paths[&(from.player, grid.worn_masks_index(from))], &grid, from.player, to.player, &mut moves);
+        moves.push(keys[pair[1]]);
+    }
+    let last = &states[last_id];
+    walk(&paths[&(last.player, grid.worn_masks_index(last))], &grid, last.player, grid.treasure, &mut moves);
+
+    Some(moves)
+}
+
+// An arrow key with crates: the player steps onto the next tile and pushes a crate on it one tile further, if that is free.
+fn step(state: &State, key: GameKey, grid: &Grid) -> Option<State> {
+    let i = DIRECTIONS.iter().position(|direction| *direction == key).unwrap();
+    let can_step = &grid.can_step[grid.worn_masks_index(state)];
+    if !can_step[state.player][i] {
+        return None;
+    }
+
+    let player = grid.neighbour(state.player, key);
+    let mut crates = state.crates.clone();
+    if let Some(index) = crates.iter().position(|tile| *tile == player) {
+        let pushed_to = grid.neighbour(player, key);
+        let covered = crates.contains(&pushed_to) || pushed_to == grid.treasure || state.masks.iter().any(|(tile, _)| *tile == pushed_to);
+        if !can_step[player][i] || covered {
+            return None;
+        }
+        crates[index] = pushed_to;
+        crates.sort_unstable();
+    }
+    Some(State { player, crates, ..state.clone() })
+}
+
+// Breadth first over single keys, see solve.
+fn solve_with_crates(level: &Level, grid: &Grid, start: State) -> Option<Vec<GameKey>> {
+    let mut states = vec![start.clone()];
+    let mut state_ids = HashMap::new();
+    state_ids.insert(start, 0);
+    let mut parents: Vec<Option<usize>> = vec![None];
+    // The key pressed to get to a state
+    let mut keys = vec![GameKey::Action];
+
+    let mut queue = VecDeque::new();
+    queue.push_back(0);
+    while let Some(id) = queue.pop_front() {
+        let state = states[id].clone();
+        if state.player == grid.treasure {
+            let mut moves = vec![];
+            let mut id = id;
+            while let Some(parent) = parents[id] {
+                moves.push(keys[id]);
+                id = parent;
+            }
+            moves.reverse();
+            return Some(moves);
+        }
+
+        let steps = DIRECTIONS.iter().filter_map(|key| step(&state, *key, grid).map(|next| (*key, next)));
+        for (key, next) in steps.chain(next_states(&state, state.player, level, grid)) {
+            if !state_ids.contains_key(&next) {
+                state_ids.insert(next.clone(), states.len());
+                states.push(next);
+                parents.push(Some(id));
+                keys.push(key);
+                queue.push_back(states.len() - 1);
+            }
+        }
+    }
+
+    None
+}
+
+/*
+ * Turns solver moves into a demo for the real game loop, one frame per step. With free
+ * movement an arrow key is followed by the opposite one a tile later (Game::steps_per_tile)
+ * to stop again, with grid movement the next key waits until the slide is over.
+ */
+pub fn moves_to_demo(moves: &[GameKey], movement: Movement) -> Demo {
+    let mut demo = Demo::new();
+    let mut frame = 0;
+    for (i, key) in moves.iter().enumerate() {
+        demo.events.push((frame, *key));
+        if *key == GameKey::Action || *key == GameKey::Cycle {
+            frame += 1;
+        } else if movement == Movement::Grid {
+            frame += Game::SLIDE_STEPS;
+        } else if i + 1 < moves.len() {
+            demo.events.push((frame + Game::steps_per_tile(), opposite(*key)));
+            frame += Game::steps_per_tile() + 1;
+        }
+    }
+    demo
+}
@@ -0,0 +1,301 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::error::AssetError;
+use crate::graphics::{DoorRule, EntityLoader};
+use crate::level_file::{self, Layer, LevelFile};
+use crate::platform::AssetSource;
+
+/*
+ * Checks a level (the terrain grid in <name>.lvl and the item grid in <name>.lvl.items)
+ * for everything Level::new_from_name silently relies on, and reports problems with
+ * their position in the file instead of panicking while the level is loaded.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: String,
+    // 1-based, 0 if the diagnostic is about the whole file
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if self.line == 0 {
+            write!(f, "{}: {}: {}", self.file, severity, self.message)
+        } else {
+            write!(f, "{}:{}:{}: {}: {}", self.file, self.line, self.column, severity, self.message)
+        }
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+// What the masks of a color are called, the name of their entity.
+fn mask_name(entity_loader: &EntityLoader, color: usize) -> String {
+    match entity_loader.mask_entity(color) {
+        Some((_, entity)) if !entity.name.is_empty() => entity.name.to_lowercase(),
+        Some((id, _)) => format!("{:?} mask", id),
+        None => format!("color {} mask", color),
+    }
+}
+
+// Lines are counted from the start of the layer and reported from the start of its file.
+struct Diagnostics<'a> {
+    file: &'a str,
+    first_line: usize,
+    list: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> Diagnostics<'a> {
+    fn new(layer: &Layer<'a>, list: &'a mut Vec<Diagnostic>) -> Self {
+        Diagnostics { file: layer.file, first_line: layer.first_line, list }
+    }
+
+    fn push(&mut self, severity: Severity, line: usize, column: usize, message: String) {
+        let line = if line == 0 { 0 } else { line + self.first_line - 1 };
+        self.list.push(Diagnostic { severity, file: self.file.to_string(), line, column, message });
+    }
+
+    fn error(&mut self, line: usize, column: usize, message: String) {
+        self.push(Severity::Error, line, column, message);
+    }
+}
+
+// Splits a layer into its rows and checks their shape.
+fn grid_rows<'b>(layer: &Layer<'b>, diagnostics: &mut Diagnostics) -> Vec<&'b [u8]> {
+    let (width, height) = (layer.width, layer.height);
+    let mut rows: Vec<&[u8]> = layer.bytes.split(|byte| *byte == b'\n').collect();
+    // A trailing newline ends the last row, it doesn't start a new one.
+    if rows.last().map_or(false, |row| row.is_empty()) {
+        rows.pop();
+    }
+
+    let mut reported_crlf = false;
+    for (y, row) in rows.iter_mut().enumerate() {
+        if let Some(stripped) = row.strip_suffix(b"\r") {
+            if !reported_crlf {
+                diagnostics.error(y + 1, stripped.len() + 1, "CRLF line ending, only LF is supported".to_string());
+                reported_crlf = true;
+            }
+            *row = stripped;
+        }
+
+        let too_short = row.len() < width && !layer.short_rows;
+        if y < height && (too_short || row.len() > width) {
+            diagnostics.error(
+                y + 1,
+                row.len().min(width) + 1,
+                format!("line has {} columns, expected {}", row.len(), width),
+            );
+        }
+    }
+
+    if rows.len() < height && !layer.short_rows {
+        diagnostics.error(rows.len() + 1, 1, format!("file has {} lines, expected {}", rows.len(), height));
+    } else if let Some(extra) = rows.iter().skip(height).position(|row| !row.is_empty()) {
+        diagnostics.error(height + extra + 1, 1, format!("unexpected content after line {}", height));
+    }
+
+    rows.truncate(height);
+    rows
+}
+
+pub fn validate_level(assets: &dyn AssetSource, entity_loader: &EntityLoader, level_name: &str) -> Vec<Diagnostic> {
+    // A single .level file is preferred over the two files of the old format.
+    let single_file_name = format!("{}.{}", level_name, level_file::EXTENSION);
+    match assets.read_file(&single_file_name, Some("levels")) {
+        Ok(bytes) => {
+            let file = AssetError::path(&single_file_name, Some("levels"));
+            return match level_file::parse(&file, &bytes) {
+                Ok(level_file) => validate_level_file(entity_loader, &level_file),
+                Err(diagnostics) => diagnostics,
+            };
+        }
+        Err(AssetError::NotFound { .. }) => {}
+        Err(e) => {
+            let file = AssetError::path(&single_file_name, Some("levels"));
+            return vec![Diagnostic { severity: Severity::Error, file, line: 0, column: 0, message: e.to_string() }];
+        }
+    }
+
+    let level_file_name = format!("{}.lvl", level_name);
+    let items_file_name = format!("{}.lvl.items", level_name);
+    let level_file = AssetError::path(&level_file_name, Some("levels"));
+    let items_file = AssetError::path(&items_file_name, Some("levels"));
+    let mut diagnostics = vec![];
+
+    let level_bytes = assets.read_file(&level_file_name, Some("levels"));
+    let items_bytes = assets.read_file(&items_file_name, Some("levels"));
+    for (file, bytes) in [(&level_file, &level_bytes), (&items_file, &items_bytes)] {
+        let message = match bytes {
+            Ok(_) => continue,
+            Err(AssetError::NotFound { .. }) => String::from("file not found"),
+            Err(e) => e.to_string(),
+        };
+        diagnostics.push(Diagnostic { severity: Severity::Error, file: file.clone(), line: 0, column: 0, message });
+    }
+
+    if let (Ok(level_bytes), Ok(items_bytes)) = (level_bytes, items_bytes) {
+        diagnostics.extend(validate_level_bytes(entity_loader, &level_file, &level_bytes, &items_file, &items_bytes));
+    }
+    diagnostics
+}
+
+// The layers of a parsed .level file, see level_file::parse for the header.
+pub fn validate_level_file(entity_loader: &EntityLoader, level_file: &LevelFile) -> Vec<Diagnostic> {
+    let mut diagnostics = level_file.warnings.clone();
+    diagnostics.extend(validate_layers(entity_loader, level_file.layer("terrain").unwrap(), level_file.layer("items").unwrap()));
+    diagnostics
+}
+
+pub fn validate_level_bytes(entity_loader: &EntityLoader, level_file: &str, level_bytes: &[u8], items_file: &str, items_bytes: &[u8]) -> Vec<Diagnostic> {
+    validate_layers(entity_loader, &Layer::new_from_file(level_file, level_bytes), &Layer::new_from_file(items_file, items_bytes))
+}
+
+pub fn validate_layers(entity_loader: &EntityLoader, terrain: &Layer, items: &Layer) -> Vec<Diagnostic> {
+    let mut list = vec![];
+
+    let mut level_diagnostics = Diagnostics::new(terrain, &mut list);
+    let level_rows = grid_rows(terrain, &mut level_diagnostics);
+
+    // Terrain: every glyph has to be an entity from entities.json.
+    let mut door_positions: Vec<(char, usize, usize)> = vec![];
+    let mut has_mixer = false;
+    let mut plates = 0;
+    for (y, row) in level_rows.iter().enumerate() {
+        for (x, byte) in row.iter().take(terrain.width).enumerate() {
+            let glyph: char = (*byte).into();
+            match entity_loader.entities.get(&glyph.to_string()) {
+                None => level_diagnostics.error(y + 1, x + 1, format!("unknown entity {:?}", glyph)),
+                Some(entity) => {
+                    has_mixer |= entity.mixer;
+                    plates += entity.plate as usize;
+                    if entity.is_door() && !door_positions.iter().any(|(door, _, _)| *door == glyph) {
+                        door_positions.push((glyph, y + 1, x + 1));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut items_diagnostics = Diagnostics::new(items, &mut list);
+    let items_rows = grid_rows(items, &mut items_diagnostics);
+
+    // Items: masks and crates, exactly one player and treasure, nothing inside a wall.
+    let mut players = vec![];
+    let mut treasures = vec![];
+    let mut mask_colors = vec![];
+    let mut crates = 0;
+    for (y, row) in items_rows.iter().enumerate() {
+        for (x, byte) in row.iter().take(terrain.width).enumerate() {
+            let item: char = (*byte).into();
+            let what = match item {
+                ' ' | '.' => continue,
+                'P' => {
+                    players.push((y + 1, x + 1));
+                    "player"
+                }
+                'T' => {
+                    treasures.push((y + 1, x + 1));
+                    "treasure"
+                }
+                _ => match (entity_loader.mask_color_from_item(item), entity_loader.item_entity(item)) {
+                    (Some(color), _) => {
+                        if !mask_colors.contains(&color) {
+                            mask_colors.push(color);
+                        }
+                        "mask"
+                    }
+                    (None, Some(entity)) if entity.pushable => {
+                        crates += 1;
+                        "crate"
+                    }
+                    _ => {
+                        // Level::new_from_name skips these, so the level still loads.
+                        items_diagnostics.push(Severity::Warning, y + 1, x + 1, format!("unknown item {:?} is ignored", item));
+                        continue;
+                    }
+                },
+            };
+
+            let terrain = level_rows.get(y).and_then(|row| row.get(x)).map(|byte| char::from(*byte));
+            if let Some(glyph) = terrain {
+                if entity_loader.entities.get(&glyph.to_string()).map_or(false, |entity| !entity.is_floor()) {
+                    items_diagnostics.error(y + 1, x + 1, format!("{} {:?} is placed on wall {:?}", what, item, glyph));
+                }
+            }
+        }
+    }
+
+    for (positions, item, what) in [(&players, 'P', "player"), (&treasures, 'T', "treasure")] {
+        match positions.first() {
+            None => items_diagnostics.error(0, 0, format!("no {} {:?}", what, item)),
+            Some((first_line, first_column)) => {
+                for (line, column) in positions.iter().skip(1) {
+                    let first_line = first_line + items.first_line - 1;
+                    items_diagnostics.error(*line, *column, format!("more than one {} {:?}, the first one is at {}:{}", what, item, first_line, first_column));
+                }
+            }
+        }
+    }
+
+    // Masks that can be mixed from those in the level count as well.
+    let mixable = |mask_colors: &[usize]| {
+        entity_loader.mixes.iter().find(|mix| !mask_colors.contains(&mix.mix) && mix.colors.iter().all(|color| mask_colors.contains(color))).map(|mix| mix.mix)
+    };
+    while let Some(mix) = mixable(&mask_colors).filter(|_| has_mixer) {
+        mask_colors.push(mix);
+    }
+
+    // Doors: warn if the masks or crates their rule needs are not in this level.
+    let mut level_diagnostics = Diagnostics::new(terrain, &mut list);
+    for (glyph, line, column) in door_positions {
+        let entity = &entity_loader.entities[&glyph.to_string()];
+        if entity.door_rule == DoorRule::Plates && plates == 0 {
+            level_diagnostics.push(Severity::Warning, line, column, format!("door {:?} can never be opened, there are no plates in this level", glyph));
+        } else if entity.door_rule == DoorRule::Plates && crates < plates {
+            let counted = |count: usize, thing: &str| if count == 1 { format!("1 {}", thing) } else { format!("{} {}s", count, thing) };
+            level_diagnostics.push(
+                Severity::Warning,
+                line,
+                column,
+                format!("door {:?} can never be opened, it needs {} for {}, the level has {}", glyph, counted(plates, "crate"), counted(plates, "plate"), counted(crates, "crate")),
+            );
+        }
+        let missing: Vec<usize> = match entity.door_rule {
+            DoorRule::Any if !entity.door_colors.iter().any(|color| mask_colors.contains(color)) => entity.door_colors.clone(),
+            DoorRule::All => entity.door_colors.iter().filter(|color| !mask_colors.contains(color)).copied().collect(),
+            _ => vec![],
+        };
+        if !missing.is_empty() {
+            let masks: Vec<String> = missing.iter().map(|color| mask_name(entity_loader, *color)).collect();
+            // Any of the masks would do, or all of them are needed.
+            let message = if entity.door_rule == DoorRule::All {
+                let which = if masks.len() == 1 { "which is" } else { "which are" };
+                format!("door {:?} can never be opened, it needs {}, {} not in this level", glyph, masks.join(" and "), which)
+            } else {
+                format!("door {:?} can never be opened, there is no {} in this level", glyph, masks.join(" or "))
+            };
+            level_diagnostics.push(Severity::Warning, line, column, message);
+        }
+    }
+
+    list
+}
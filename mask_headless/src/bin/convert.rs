@@ -0,0 +1,98 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use mask_core::game;
+use mask_core::graphics::{EntityLoader, LevelPack};
+use mask_core::level_file;
+use mask_core::validate::{has_errors, validate_level_file};
+use mask_headless::file_loader::FileLoader;
+
+const USAGE: &str = "usage: convert [--assets DIR] [--remove-old] [LEVEL...]";
+
+/*
+ * Converts levels from the two-file format (<name>.lvl and <name>.lvl.items) to a single
+ * <name>.level file, with the level's name from pack.json in the header.
+ */
+fn main() {
+    let mut assets_dir = String::from("assets");
+    let mut remove_old = false;
+    let mut level_names = vec![];
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => assets_dir = args.next().unwrap_or_else(|| usage_error("missing value for --assets")),
+            "--remove-old" => remove_old = true,
+            _ if arg.starts_with("--") => usage_error(&format!("unknown argument {}", arg)),
+            _ => level_names.push(arg),
+        }
+    }
+
+    let file_loader = FileLoader::new(&assets_dir);
+    let pack = LevelPack::new(&file_loader).unwrap_or_else(|e| fail(&e.to_string()));
+    if level_names.is_empty() {
+        level_names = game::level_names(&file_loader).unwrap_or_else(|e| fail(&e.to_string()));
+    }
+
+    let levels_dir = Path::new(&assets_dir).join("levels");
+    let mut failed = false;
+    for level_name in level_names {
+        let level_path = levels_dir.join(format!("{}.lvl", level_name));
+        let items_path = levels_dir.join(format!("{}.lvl.items", level_name));
+        let (level_bytes, items_bytes) = match (fs::read(&level_path), fs::read(&items_path)) {
+            (Ok(level_bytes), Ok(items_bytes)) => (level_bytes, items_bytes),
+            _ => {
+                println!("level {}: no {} and {}, skipped", level_name, level_path.display(), items_path.display());
+                continue;
+            }
+        };
+
+        let pack_level = pack.level(&level_name);
+        let mut metadata = vec![];
+        if let Some(name) = &pack_level.name {
+            metadata.push((String::from("name"), name.clone()));
+        }
+        let text = level_file::convert(&level_bytes, &items_bytes, &metadata);
+
+        // Only replace the old files with something that loads.
+        let single_path = levels_dir.join(format!("{}.{}", level_name, level_file::EXTENSION));
+        let file = single_path.display().to_string();
+        let entity_loader = EntityLoader::new_from_file(&file_loader, pack_level.entities_file()).unwrap_or_else(|e| fail(&e.to_string()));
+        let diagnostics = match level_file::parse(&file, text.as_bytes()) {
+            Ok(parsed) => validate_level_file(&entity_loader, &parsed),
+            Err(diagnostics) => diagnostics,
+        };
+        for diagnostic in diagnostics.iter() {
+            println!("{}", diagnostic);
+        }
+        if has_errors(&diagnostics) {
+            println!("level {}: not converted", level_name);
+            failed = true;
+            continue;
+        }
+
+        fs::write(&single_path, text).unwrap_or_else(|e| fail(&format!("Could not write {}: {}", file, e)));
+        if remove_old {
+            for path in [&level_path, &items_path] {
+                fs::remove_file(path).unwrap_or_else(|e| fail(&format!("Could not remove {}: {}", path.display(), e)));
+            }
+        }
+        println!("level {}: written to {}", level_name, file);
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -0,0 +1,97 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use mask_core::graphics::{EntityLoader, Level, LevelPack};
+use mask_core::platform::GameKey;
+use mask_core::solver::{moves_to_demo, solve};
+use mask_headless::file_loader::FileLoader;
+
+const USAGE: &str = "usage: solve [--assets DIR] [--demo-dir DIR] [LEVEL...]";
+
+// Prints the moves run-length encoded, e.g. "right x12, up x3, space".
+fn format_moves(moves: &[GameKey]) -> String {
+    let mut runs: Vec<(GameKey, usize)> = vec![];
+    for key in moves {
+        match runs.last_mut() {
+            Some((last, count)) if last == key => *count += 1,
+            _ => runs.push((*key, 1)),
+        }
+    }
+
+    runs.iter()
+        .map(|(key, count)| if *count == 1 { key.name().to_string() } else { format!("{} x{}", key.name(), count) })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn main() {
+    let mut assets_dir = String::from("assets");
+    let mut demo_dir = None;
+    let mut level_names = vec![];
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => assets_dir = args.next().unwrap_or_else(|| usage_error("missing value for --assets")),
+            "--demo-dir" => demo_dir = Some(args.next().unwrap_or_else(|| usage_error("missing value for --demo-dir"))),
+            _ if arg.starts_with("--") => usage_error(&format!("unknown argument {}", arg)),
+            _ => level_names.push(arg),
+        }
+    }
+
+    let file_loader = FileLoader::new(assets_dir);
+    let pack = LevelPack::new(&file_loader).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let pack_levels = if level_names.is_empty() {
+        pack.levels
+    } else {
+        level_names.iter().map(|level_name| pack.level(level_name)).collect()
+    };
+
+    let mut all_solvable = true;
+    for pack_level in pack_levels {
+        let level_name = &pack_level.level;
+        let entity_loader = EntityLoader::new_from_file(&file_loader, pack_level.entities_file()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        let level = match Level::new_from_name(&file_loader, &entity_loader, level_name) {
+            Ok(level) => level,
+            Err(e) => {
+                println!("level {}: could not be loaded:\n{}", level_name, e);
+                all_solvable = false;
+                continue;
+            }
+        };
+        match solve(&level) {
+            Some(moves) => {
+                let par = match pack_level.par_moves {
+                    Some(par_moves) => format!(" (par {})", par_moves),
+                    None => String::new(),
+                };
+                println!("level {}: solvable in {} moves{}: {}", level_name, moves.len(), par, format_moves(&moves));
+                if let Some(dir) = &demo_dir {
+                    let path = format!("{}/{}.demo", dir, level_name);
+                    fs::create_dir_all(dir).unwrap();
+                    fs::write(&path, moves_to_demo(&moves, pack.movement).to_text()).unwrap_or_else(|e| panic!("Could not write {}: {}", path, e));
+                }
+            }
+            None => {
+                println!("level {}: unsolvable", level_name);
+                all_solvable = false;
+            }
+        }
+    }
+
+    if !all_solvable {
+        process::exit(1);
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,55 @@
+use std::env;
+use std::process;
+
+use mask_core::graphics::{EntityLoader, LevelPack};
+use mask_core::validate::{has_errors, validate_level};
+use mask_headless::file_loader::FileLoader;
+
+const USAGE: &str = "usage: validate [--assets DIR] [LEVEL...]";
+
+fn main() {
+    let mut assets_dir = String::from("assets");
+    let mut level_names = vec![];
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => assets_dir = args.next().unwrap_or_else(|| usage_error("missing value for --assets")),
+            _ if arg.starts_with("--") => usage_error(&format!("unknown argument {}", arg)),
+            _ => level_names.push(arg),
+        }
+    }
+
+    let file_loader = FileLoader::new(assets_dir);
+    let pack = LevelPack::new(&file_loader).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let pack_levels = if level_names.is_empty() {
+        pack.levels
+    } else {
+        level_names.iter().map(|level_name| pack.level(level_name)).collect()
+    };
+
+    let mut failed = false;
+    for pack_level in pack_levels {
+        let entity_loader = EntityLoader::new_from_file(&file_loader, pack_level.entities_file()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        let diagnostics = validate_level(&file_loader, &entity_loader, &pack_level.level);
+        for diagnostic in diagnostics.iter() {
+            println!("{}", diagnostic);
+        }
+        failed |= has_errors(&diagnostics);
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
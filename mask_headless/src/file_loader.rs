@@ -0,0 +1,51 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use mask_core::error::AssetError;
+use mask_core::platform::AssetSource;
+
+/// Reads assets from a directory on the host, laid out like the EFI volume.
+pub struct FileLoader {
+    root: PathBuf,
+}
+
+impl FileLoader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileLoader {
+            root: root.into(),
+        }
+    }
+}
+
+impl AssetSource for FileLoader {
+    fn read_file(&self, file_name: &str, directory: Option<&str>) -> Result<Vec<u8>, AssetError> {
+        let file = AssetError::path(file_name, directory);
+        let path = self.root.join(&file);
+        if path.is_dir() {
+            return Err(AssetError::NotARegularFile { file });
+        }
+
+        fs::read(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => AssetError::NotFound { file },
+            _ => AssetError::Io { file, message: "could not read file" },
+        })
+    }
+
+    fn list_directory(&self, directory: &str) -> Result<Vec<String>, AssetError> {
+        let file = String::from(directory);
+        let entries = fs::read_dir(self.root.join(directory)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => AssetError::NotFound { file: file.clone() },
+            _ => AssetError::Io { file: file.clone(), message: "could not read directory" },
+        })?;
+
+        let mut names = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|_| AssetError::Io { file: file.clone(), message: "could not read directory" })?;
+            if entry.path().is_file() {
+                names.extend(entry.file_name().into_string());
+            }
+        }
+        Ok(names)
+    }
+}
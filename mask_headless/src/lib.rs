@@ -0,0 +1,2 @@
+pub mod file_loader;
+pub mod platform;
@@ -0,0 +1,90 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use mask_core::demo::{Demo, ReplayInput};
+use mask_core::game::Game;
+use mask_core::graphics::{LevelPack, VirtualFrameBuffer};
+use mask_headless::file_loader::FileLoader;
+use mask_headless::platform::FrameDumper;
+
+const USAGE: &str = "usage: mask_headless [--assets DIR] [--level NAME] [--demo FILE] [--frames N] [--dump-every N] [--out DIR]";
+
+fn main() {
+    let mut assets_dir = String::from("assets");
+    let mut level_name = None;
+    let mut demo_file = None;
+    let mut frames = 600;
+    let mut dump_every = 0;
+    let mut out_dir = String::from("frames");
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage_error(&format!("missing value for {}", arg)));
+        match arg.as_str() {
+            "--assets" => assets_dir = value(),
+            "--level" => level_name = Some(value()),
+            "--demo" => demo_file = Some(value()),
+            "--frames" => frames = value().parse().unwrap_or_else(|_| usage_error("--frames expects a number")),
+            "--dump-every" => dump_every = value().parse().unwrap_or_else(|_| usage_error("--dump-every expects a number")),
+            "--out" => out_dir = value(),
+            _ => usage_error(&format!("unknown argument {}", arg)),
+        }
+    }
+
+    let demo = match demo_file {
+        Some(path) => {
+            let text = fs::read_to_string(&path).unwrap_or_else(|e| fail(&format!("Could not read {}: {}", path, e)));
+            Demo::parse(&text).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+        }
+        None => Demo::new(),
+    };
+    let mut input = ReplayInput::new(demo);
+
+    let file_loader = FileLoader::new(assets_dir);
+    let mut display = FrameDumper::new(out_dir, dump_every);
+    let mut vfb = VirtualFrameBuffer::new();
+    let pack = LevelPack::new(&file_loader).unwrap_or_else(|e| fail(&e.to_string()));
+    let level_index = match level_name {
+        Some(name) => pack.levels.iter().position(|level| level.level == name).unwrap_or_else(|| fail(&format!("no level {}", name))),
+        None => 0,
+    };
+    let mut game = Game::new_at_level(&file_loader, level_index).unwrap_or_else(|e| fail(&e.to_string()));
+
+    for _ in 0..frames {
+        if let Err(e) = game.step(&file_loader, &mut input, &mut display, &mut vfb) {
+            display.dump(&vfb, "final");
+            fail(&e.to_string());
+        }
+        if game.escaped {
+            break;
+        }
+    }
+
+    display.dump(&vfb, "final");
+
+    if !input.is_finished() {
+        eprintln!("warning: demo has events after frame {}", frames);
+    }
+    for (pack_level, moves) in game.pack.levels[level_index..].iter().zip(game.solved_moves.iter()) {
+        match pack_level.par_moves {
+            Some(par_moves) => println!("level {} solved in {} moves, par {}", pack_level.level, moves, par_moves),
+            None => println!("level {} solved in {} moves", pack_level.level, moves),
+        }
+    }
+    if game.escaped {
+        println!("escaped after the last level {}", game.level_name());
+    } else {
+        println!("level {}, player at ({}, {})", game.level_name(), game.level.player.sprite.pos.x(), game.level.player.sprite.pos.y());
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -0,0 +1,38 @@
+use std::fs;
+use std::path::PathBuf;
+
+use mask_core::graphics::VirtualFrameBuffer;
+use mask_core::platform::Display;
+
+/// Writes presented frames as PPM images into a directory.
+pub struct FrameDumper {
+    out_dir: PathBuf,
+    every: usize,
+    frame: usize,
+}
+
+impl FrameDumper {
+    /// Dumps every `every`-th frame, or none at all if `every` is 0.
+    pub fn new(out_dir: impl Into<PathBuf>, every: usize) -> Self {
+        FrameDumper {
+            out_dir: out_dir.into(),
+            every,
+            frame: 0,
+        }
+    }
+
+    pub fn dump(&self, vfb: &VirtualFrameBuffer, name: &str) {
+        let path = self.out_dir.join(format!("{}.ppm", name));
+        fs::create_dir_all(&self.out_dir).expect("Could not create output directory");
+        fs::write(&path, vfb.to_ppm()).unwrap_or_else(|e| panic!("Could not write {}: {}", path.display(), e));
+    }
+}
+
+impl Display for FrameDumper {
+    fn present(&mut self, vfb: &VirtualFrameBuffer) {
+        if self.every != 0 && self.frame % self.every == 0 {
+            self.dump(vfb, &format!("frame_{:06}", self.frame));
+        }
+        self.frame += 1;
+    }
+}
@@ -0,0 +1,62 @@
+use std::fs;
+
+use mask_core::error::AssetError;
+use mask_core::game::Game;
+use mask_core::graphics::{EntityLoader, Level, TileSet};
+
+mod common;
+use common::{assets_dir, PatchedAssets};
+
+#[test]
+fn missing_level_is_not_found() {
+    let assets = PatchedAssets::new().with("levels/1.lvl.items", None);
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let error = Level::new_from_name(&assets, &entity_loader, "1").err().unwrap();
+    assert_eq!(error, AssetError::NotFound { file: "levels/1.lvl.items".to_string() });
+    assert_eq!(error.to_string(), "levels/1.lvl.items: file not found");
+}
+
+#[test]
+fn broken_level_is_refused() {
+    let level = fs::read_to_string(assets_dir().join("levels/0.lvl")).unwrap();
+    let assets = PatchedAssets::new().with("levels/0.lvl", Some(&level.replacen('E', "?", 1)));
+    let error = Game::new(&assets).err().unwrap();
+    assert_eq!(error.to_string(), "levels/0.lvl:2:2: error: unknown entity '?'");
+}
+
+#[test]
+fn bad_entities_json() {
+    let assets = PatchedAssets::new().with("entities.json", Some("{\"P\": {\"tile_x\": true}}"));
+    let error = EntityLoader::new(&assets).err().unwrap();
+    assert_eq!(error.to_string(), "entities.json: entity 'P': tile_x is not a number");
+
+    let assets = PatchedAssets::new().with("entities.json", Some("{\"P\": {}"));
+    assert_eq!(EntityLoader::new(&assets).err().unwrap().to_string(), "entities.json: file is not valid JSON");
+
+    let assets = PatchedAssets::new().with("entities.json", Some("{\"P\": {}, \"T\": {}}"));
+    assert_eq!(EntityLoader::new(&assets).err().unwrap().to_string(), "entities.json: entity 'r' is missing");
+
+    let assets = PatchedAssets::new().with("entities.json", Some("{\"m\": {\"mixed_from\": [0, 2]}}"));
+    assert_eq!(EntityLoader::new(&assets).err().unwrap().to_string(), "entities.json: entity 'm': mixed_from needs two colors and a mask_color to mix them to");
+
+    let assets = PatchedAssets::new().with("entities.json", Some("{\"P\": {}, \"T\": {}, \"r\": {}, \"g\": {}, \"b\": {}, \"x\": {\"mask_color\": 1}}"));
+    assert_eq!(EntityLoader::new(&assets).err().unwrap().to_string(), "entities.json: entity 'x': mask_color 1 is already the color of entity 'g'");
+
+    let assets = PatchedAssets::new().with("entities.json", Some("{\"j\": {\"door_rule\": \"some\"}}"));
+    assert_eq!(EntityLoader::new(&assets).err().unwrap().to_string(), "entities.json: entity 'j': unknown door_rule 'some', expected \"any\", \"all\", \"not\", \"bare\" or \"plates\"");
+
+    let assets = PatchedAssets::new().with("entities.json", Some("{\"h\": {\"revealed_by\": [0]}}"));
+    assert_eq!(EntityLoader::new(&assets).err().unwrap().to_string(), "entities.json: entity 'h': revealed and revealed_by need each other");
+}
+
+#[test]
+fn unknown_entity_is_an_error() {
+    let entity_loader = EntityLoader::new(&PatchedAssets::new()).unwrap();
+    assert_eq!(entity_loader.get("?").err().unwrap().to_string(), "entities.json: entity '?' is missing");
+}
+
+#[test]
+fn bad_tile_set() {
+    let assets = PatchedAssets::new().with("TileSet.bmp", Some("not a bitmap"));
+    assert!(matches!(TileSet::new_from_file(&assets, "TileSet.bmp"), Err(AssetError::BadBmp { .. })));
+}
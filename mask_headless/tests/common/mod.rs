@@ -0,0 +1,72 @@
+// Each test binary uses its own part of the fixture.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use mask_core::error::AssetError;
+use mask_core::platform::AssetSource;
+use mask_headless::file_loader::FileLoader;
+
+pub fn assets_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets")
+}
+
+// The shipped assets as they are.
+pub fn file_loader() -> FileLoader {
+    FileLoader::new(assets_dir())
+}
+
+// The shipped assets with a pack of only levels/test.level, played with movement.
+pub fn test_level(movement: &str, level: &str) -> PatchedAssets {
+    PatchedAssets::new()
+        .with("pack.json", Some(&format!(r#"{{ "movement": "{}", "levels": [ {{ "level": "test" }} ] }}"#, movement)))
+        .with("levels/test.level", Some(level))
+}
+
+// The shipped assets, with single files replaced or removed and extra entities added to entities.json.
+pub struct PatchedAssets {
+    file_loader: FileLoader,
+    files: HashMap<String, Option<Vec<u8>>>,
+    extra_entities: Vec<String>,
+}
+
+impl PatchedAssets {
+    pub fn new() -> Self {
+        PatchedAssets { file_loader: file_loader(), files: HashMap::new(), extra_entities: vec![] }
+    }
+
+    pub fn with(mut self, path: &str, content: Option<&str>) -> Self {
+        self.files.insert(path.to_string(), content.map(|content| content.as_bytes().to_vec()));
+        self
+    }
+
+    // Comma separated "id": { ... } members, appended to the entities object.
+    pub fn with_entities(mut self, entities: &str) -> Self {
+        if !entities.is_empty() {
+            self.extra_entities.push(entities.to_string());
+        }
+        self
+    }
+}
+
+impl AssetSource for PatchedAssets {
+    fn read_file(&self, file_name: &str, directory: Option<&str>) -> Result<Vec<u8>, AssetError> {
+        let file = AssetError::path(file_name, directory);
+        let content = match self.files.get(&file) {
+            Some(Some(content)) => content.clone(),
+            Some(None) => return Err(AssetError::NotFound { file }),
+            None => self.file_loader.read_file(file_name, directory)?,
+        };
+        if file != "entities.json" || self.extra_entities.is_empty() {
+            return Ok(content);
+        }
+
+        let entities = String::from_utf8(content).unwrap();
+        Ok(format!("{},\n{}\n}}", entities.trim_end().trim_end_matches('}'), self.extra_entities.join(",\n")).into_bytes())
+    }
+
+    fn list_directory(&self, directory: &str) -> Result<Vec<String>, AssetError> {
+        self.file_loader.list_directory(directory)
+    }
+}
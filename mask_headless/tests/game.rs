@@ -0,0 +1,340 @@
+use std::cell::Cell;
+
+use mask_core::demo::ReplayInput;
+use mask_core::error::AssetError;
+use mask_core::game::{level_names, Game, StepTimer};
+use mask_core::graphics::{Camera, DrawFramebuffer, EntityLoader, Level, LevelPack, Movement, Rect, Sprite, Tile, TileSet, VirtualFrameBuffer};
+use mask_core::math::Vec2;
+use mask_core::platform::{AssetSource, Clock, GameKey, Input};
+use mask_core::solver::{moves_to_demo, solve};
+
+mod common;
+use common::{file_loader, test_level, PatchedAssets};
+
+// Only a directory listing, for level_names.
+struct Listing(Vec<&'static str>);
+
+impl AssetSource for Listing {
+    fn read_file(&self, file_name: &str, directory: Option<&str>) -> Result<Vec<u8>, AssetError> {
+        Err(AssetError::NotFound { file: AssetError::path(file_name, directory) })
+    }
+
+    fn list_directory(&self, _directory: &str) -> Result<Vec<String>, AssetError> {
+        Ok(self.0.iter().map(|name| name.to_string()).collect())
+    }
+}
+
+#[test]
+fn levels_are_sorted_by_number() {
+    let listing = Listing(vec!["10.lvl", "10.lvl.items", "9.lvl", "bonus.lvl", "0.lvl", "README", "2.lvl", "attic.lvl"]);
+    assert_eq!(level_names(&listing).unwrap(), ["0", "2", "9", "10", "attic", "bonus"]);
+}
+
+#[test]
+fn last_level_ends_in_escape() {
+    let file_loader = file_loader();
+    let last = LevelPack::new(&file_loader).unwrap().levels.len() - 1;
+    let mut game = Game::new_at_level(&file_loader, last).unwrap();
+    let moves = solve(&game.level).unwrap();
+    let demo = moves_to_demo(&moves, game.movement());
+    let frames = demo.events.last().unwrap().0 + 32;
+
+    // Game::step without rendering, which is slow in debug builds.
+    let mut input = ReplayInput::new(demo);
+    for _ in 0..frames {
+        if let Some(key) = input.read_key() {
+            game.handle_key(key);
+        }
+        game.update(&file_loader).unwrap();
+        if game.escaped {
+            break;
+        }
+    }
+
+    assert!(game.escaped, "still in level {}", game.level_name());
+    assert_eq!(game.level_index, last);
+    // The game counts moves like the solver does.
+    assert_eq!(game.solved_moves, [moves.len()]);
+}
+
+#[test]
+fn pack_lists_levels_with_metadata() {
+    let pack = LevelPack::new_from_buffer(
+        "pack.json",
+        br#"{
+            "name": "Test pack",
+            "levels": [
+                { "level": "3", "name": "Crossroads", "intro": "Hello", "par_moves": 116 },
+                { "level": "0", "tile_set": "Night.bmp", "entities": "night.json" }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(pack.name.as_deref(), Some("Test pack"));
+    assert_eq!(pack.levels.len(), 2);
+    assert_eq!(pack.levels[0].display_name(), "Crossroads");
+    assert_eq!(pack.levels[0].intro.as_deref(), Some("Hello"));
+    assert_eq!(pack.levels[0].par_moves, Some(116));
+    assert_eq!(pack.levels[0].tile_set_file(), "TileSet.bmp");
+    assert_eq!(pack.levels[1].display_name(), "0");
+    assert_eq!(pack.levels[1].tile_set_file(), "Night.bmp");
+    assert_eq!(pack.levels[1].entities_file(), "night.json");
+}
+
+#[test]
+fn pack_errors_name_the_level() {
+    let error = LevelPack::new_from_buffer("pack.json", br#"{ "levels": [ { "level": "0" }, { "name": "Nameless" } ] }"#).err().unwrap();
+    assert_eq!(error.to_string(), "pack.json: level 2: level is missing");
+
+    let error = LevelPack::new_from_buffer("pack.json", br#"{ "levels": [ { "level": "0", "par_moves": "few" } ] }"#).err().unwrap();
+    assert_eq!(error.to_string(), "pack.json: level 1: par_moves is not a number");
+}
+
+#[test]
+fn shipped_pack_par_moves_are_reachable() {
+    let file_loader = file_loader();
+    let pack = LevelPack::new(&file_loader).unwrap();
+
+    for pack_level in pack.levels.iter() {
+        let entity_loader = EntityLoader::new_from_file(&file_loader, pack_level.entities_file()).unwrap();
+        let level = Level::new_from_name(&file_loader, &entity_loader, &pack_level.level).unwrap();
+        let moves = solve(&level).unwrap();
+        if let Some(par_moves) = pack_level.par_moves {
+            assert!(moves.len() <= par_moves, "level {}: par {} is below the {} moves the solver needs", pack_level.level, par_moves, moves.len());
+        }
+    }
+}
+
+#[test]
+fn only_changed_sprites_are_redrawn() {
+    let file_loader = file_loader();
+    let mut game = Game::new_at_level(&file_loader, 1).unwrap();
+    let mut vfb = VirtualFrameBuffer::new();
+    vfb.dirty.clear();
+
+    game.render(&mut vfb);
+    assert_eq!(vfb.dirty, [vfb.rect()]);
+    vfb.dirty.clear();
+
+    game.render(&mut vfb);
+    assert!(vfb.dirty.is_empty());
+
+    // The tile the player left and the one it is on now.
+    let start = game.level.player.sprite.pos;
+    game.handle_key(GameKey::Right);
+    while game.level.player.sprite.pos.x() == start.x() {
+        game.update(&file_loader).unwrap();
+    }
+    game.render(&mut vfb);
+    assert_eq!(vfb.dirty, [Rect::new(start.x(), start.y(), 16, 16), Rect::new(start.x() + 1, start.y(), 16, 16)]);
+
+    // Drawing only those leaves the same picture as drawing everything.
+    let mut expected = VirtualFrameBuffer::new();
+    game.level.draw(&game.tile_set, &mut expected);
+    assert!(vfb.to_ppm() == expected.to_ppm());
+}
+
+#[test]
+fn sprites_beside_the_screen_are_not_drawn() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+    let tile_set = TileSet::new_from_file(&file_loader, TileSet::FILE_NAME).unwrap();
+    let mut vfb = VirtualFrameBuffer::new();
+    let expected = vfb.to_ppm();
+
+    // On rows that are shown, but right and left of the screen.
+    let mut sprite = Sprite::new(vec![vec![entity_loader.get("P").unwrap().clone()]]);
+    for x in [VirtualFrameBuffer::WIDTH as i32 + 100, -100] {
+        sprite.pos = Vec2::new(x, 16);
+        sprite.draw_at(&tile_set, &mut vfb, Vec2::new(0, 0));
+    }
+    assert!(vfb.to_ppm() == expected);
+}
+
+struct FakeClock(Cell<u64>);
+
+impl Clock for FakeClock {
+    fn now_micros(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+#[test]
+fn step_timer_runs_the_steps_that_are_due() {
+    let clock = FakeClock(Cell::new(5_000_000));
+    let mut step_timer = StepTimer::new(&clock);
+    let micros_per_step = 1_000_000 / Game::STEPS_PER_SECOND;
+    let mut advance = |micros: u64| {
+        clock.0.set(clock.0.get() + micros);
+        step_timer.due_steps(&clock)
+    };
+
+    assert_eq!(advance(0), 0);
+    assert_eq!(advance(micros_per_step * 5 / 2), 2);
+    // The half step left over adds up with the next frame.
+    assert_eq!(advance(micros_per_step / 2), 1);
+    assert_eq!(advance(micros_per_step - 1), 0);
+    // No catching up after a long stall
+    assert_eq!(advance(1_000_000), StepTimer::MAX_STEPS_PER_FRAME);
+    assert_eq!(advance(micros_per_step), 1);
+}
+
+#[test]
+fn player_walks_a_tile_in_steps_per_tile() {
+    let file_loader = file_loader();
+    let mut game = Game::new(&file_loader).unwrap();
+    let key = solve(&game.level).unwrap()[0];
+    let start = game.level.player.sprite.pos;
+
+    game.handle_key(key);
+    for _ in 0..Game::steps_per_tile() {
+        game.update(&file_loader).unwrap();
+    }
+
+    let moved = game.level.player.sprite.pos - start;
+    assert_eq!(moved.x().abs() + moved.y().abs(), 16);
+    assert_eq!(game.moves, 1);
+}
+
+// Shipped levels, with grid movement.
+const GRID_PACK: &str = r#"{ "movement": "grid", "levels": [ { "level": "1" }, { "level": "3" } ] }"#;
+
+#[test]
+fn grid_movement_moves_one_tile_per_key() {
+    let assets = PatchedAssets::new().with("pack.json", Some(GRID_PACK));
+    let mut game = Game::new(&assets).unwrap();
+    assert_eq!(game.movement(), Movement::Grid);
+
+    // Walls and the level edge don't take a move.
+    for key in [GameKey::Left, GameKey::Right, GameKey::Up, GameKey::Down] {
+        let start = game.level.player.sprite.pos;
+        game.handle_key(key);
+        for _ in 0..Game::SLIDE_STEPS {
+            game.update(&assets).unwrap();
+        }
+        let moved = game.level.player.sprite.pos - start;
+        if moved.x() == 0 && moved.y() == 0 {
+            assert_eq!(game.moves, 0);
+        } else {
+            assert_eq!(moved.x().abs() + moved.y().abs(), 16);
+            assert_eq!(game.moves, 1);
+            break;
+        }
+    }
+    assert_eq!(game.moves, 1);
+
+    // The player stops on the tile.
+    let start = game.level.player.sprite.pos;
+    game.update(&assets).unwrap();
+    assert_eq!(game.level.player.sprite.pos.x(), start.x());
+    assert_eq!(game.level.player.sprite.pos.y(), start.y());
+}
+
+#[test]
+fn grid_movement_demo_solves_the_pack() {
+    let assets = PatchedAssets::new().with("pack.json", Some(GRID_PACK));
+    let mut game = Game::new(&assets).unwrap();
+    let mut expected_moves = vec![];
+
+    while !game.escaped {
+        let level_index = game.level_index;
+        let moves = solve(&game.level).unwrap();
+        expected_moves.push(moves.len());
+        let mut input = ReplayInput::new(moves_to_demo(&moves, Movement::Grid));
+        while game.level_index == level_index && !game.escaped {
+            if let Some(key) = input.read_key() {
+                game.handle_key(key);
+            }
+            game.update(&assets).unwrap();
+        }
+    }
+    assert_eq!(game.solved_moves, expected_moves);
+}
+
+#[test]
+fn pack_movement_must_be_known() {
+    let pack = LevelPack::new_from_buffer("pack.json", br#"{ "levels": [ { "level": "0" } ] }"#).unwrap();
+    assert_eq!(pack.movement, Movement::Free);
+
+    let error = LevelPack::new_from_buffer("pack.json", br#"{ "movement": "hex", "levels": [ { "level": "0" } ] }"#).err().unwrap();
+    assert_eq!(error.to_string(), "pack.json: unknown movement 'hex', expected \"free\" or \"grid\"");
+}
+
+#[test]
+fn putting_on_a_mask_redraws_hidden_tiles() {
+    // A wall that is a passage for the red mask.
+    let level = "MaskOS level\nwidth: 5\nheight: 3\n\n[terrain]\nBBBBB\nDEhEF\nHHHHH\n[items]\n\n P T\n";
+    let assets = test_level("free", level).with_entities(r#""h": { "tile_x": 16, "tile_y": 1, "wall": true, "revealed_by": [0], "revealed": "E" }"#);
+    let mut game = Game::new(&assets).unwrap();
+    let mut vfb = VirtualFrameBuffer::new();
+    game.render(&mut vfb);
+    vfb.dirty.clear();
+
+    let camera = Camera::new_following(&game.level, vfb.width(), vfb.height());
+    let tile_pixel = |id: &str| {
+        let entity = game.entity_loader.get(id).unwrap();
+        game.tile_set.tiles[entity.tile_x as usize][entity.tile_y as usize].pixels[8][8]
+    };
+    let (hidden, floor) = (tile_pixel("h"), tile_pixel("E"));
+    assert_ne!(hidden, floor);
+    let (x, y) = ((2 * Tile::WIDTH) as i32 - camera.pos.x() + 8, Tile::HEIGHT as i32 - camera.pos.y() + 8);
+    assert_eq!(vfb.pixel(x as usize, y as usize), hidden);
+
+    // Nothing moved, but the passage shows.
+    game.level.player.masks = vec![0];
+    game.render(&mut vfb);
+    assert_eq!(vfb.dirty, [vfb.rect()]);
+    assert_eq!(vfb.pixel(x as usize, y as usize), floor);
+}
+
+// A crate in a corridor that ends at the treasure.
+const CRATE_CORRIDOR: &str = "MaskOS level\nwidth: 7\nheight: 3\n\n[terrain]\nBBBBBBB\nDEEEEEF\nHHHHHHH\n[items]\n\n PC  T\n";
+
+#[test]
+fn grid_movement_pushes_crates_along() {
+    let assets = test_level("grid", CRATE_CORRIDOR);
+    let mut game = Game::new(&assets).unwrap();
+    let step_right = |game: &mut Game| {
+        game.handle_key(GameKey::Right);
+        for _ in 0..Game::SLIDE_STEPS {
+            game.update(&assets).unwrap();
+        }
+    };
+
+    // The crate slides along with the player, a tile per key.
+    for tile in [3, 4] {
+        step_right(&mut game);
+        assert_eq!(game.level.crates[0].sprite.pos.x(), tile * Tile::WIDTH as i32);
+        assert_eq!(game.level.player.sprite.pos.x(), (tile - 1) * Tile::WIDTH as i32);
+    }
+    assert_eq!(game.moves, 2);
+
+    // It is not pushed onto the treasure, the player doesn't get past it.
+    step_right(&mut game);
+    assert_eq!(game.level.crates[0].sprite.pos.x(), 4 * Tile::WIDTH as i32);
+    assert_eq!(game.moves, 2);
+    assert!(solve(&game.level).is_none());
+}
+
+#[test]
+fn free_movement_pushes_crates_a_whole_tile() {
+    let assets = test_level("free", CRATE_CORRIDOR);
+    let mut game = Game::new(&assets).unwrap();
+    let walk_right = |game: &mut Game, pixels: i32| {
+        game.handle_key(GameKey::Right);
+        for _ in 0..pixels {
+            game.update(&assets).unwrap();
+        }
+        game.handle_key(GameKey::Left);
+    };
+
+    // The first pixel into the crate pushes it onto the next tile, stopping half way doesn't leave it between tiles.
+    walk_right(&mut game, 13);
+    assert_eq!(game.level.player.sprite.pos.x(), Tile::WIDTH as i32 + 13);
+    assert_eq!(game.level.crates[0].sprite.pos.x(), 3 * Tile::WIDTH as i32);
+
+    walk_right(&mut game, 4);
+    assert_eq!(game.level.player.sprite.pos.x(), 2 * Tile::WIDTH as i32 + 1);
+    assert_eq!(game.level.crates[0].sprite.pos.x(), 4 * Tile::WIDTH as i32);
+}
@@ -0,0 +1,171 @@
+use std::fs;
+
+use mask_core::game::level_names;
+use mask_core::graphics::{Camera, EntityLoader, Level, TileSet, VirtualFrameBuffer};
+use mask_core::math::Vec2;
+use mask_core::level_file::{self, Layer};
+use mask_core::validate::{has_errors, validate_layers, validate_level, Diagnostic};
+
+mod common;
+use common::{assets_dir, file_loader, PatchedAssets};
+
+fn converted(level_name: &str) -> String {
+    let level_bytes = fs::read(assets_dir().join(format!("levels/{}.lvl", level_name))).unwrap();
+    let items_bytes = fs::read(assets_dir().join(format!("levels/{}.lvl.items", level_name))).unwrap();
+    level_file::convert(&level_bytes, &items_bytes, &[(String::from("name"), String::from("Test"))])
+}
+
+// Everything about a level that the game plays with.
+fn describe(level: &Level) -> String {
+    let tiles: Vec<(u8, u8, bool)> = level.sprite.entities.iter().flatten().map(|entity| (entity.tile_x, entity.tile_y, entity.wall)).collect();
+    let masks: Vec<(i32, i32, usize)> = level.masks.iter().map(|mask| (mask.sprite.pos.x(), mask.sprite.pos.y(), mask.mask_color)).collect();
+    format!(
+        "{:?} player {:?} treasure {:?} masks {:?}",
+        tiles,
+        (level.player.sprite.pos.x(), level.player.sprite.pos.y()),
+        (level.treasure.sprite.pos.x(), level.treasure.sprite.pos.y()),
+        masks
+    )
+}
+
+fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+    diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect()
+}
+
+#[test]
+fn converted_levels_load_like_the_originals() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+
+    for level_name in level_names(&file_loader).unwrap() {
+        let original = Level::new_from_name(&file_loader, &entity_loader, &level_name).unwrap();
+
+        let single_file = format!("levels/{}.level", level_name);
+        let assets = PatchedAssets::new().with(&single_file, Some(&converted(&level_name)));
+        let diagnostics = validate_level(&assets, &entity_loader, &level_name);
+        assert!(!has_errors(&diagnostics), "{:#?}", messages(&diagnostics));
+        let level = Level::new_from_name(&assets, &entity_loader, &level_name).unwrap();
+
+        assert_eq!(describe(&level), describe(&original), "level {}", level_name);
+    }
+}
+
+#[test]
+fn diagnostics_use_lines_of_the_level_file() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+    let text = converted("0");
+    // Header: magic, width, height, name, empty line, [terrain]; so row 4 is line 10.
+    let text = text.replacen("DEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEF", "DEEEEEEEEE?EEEEEEEEEEEEEEEEEEEEEEEEEEEEF", 3);
+    let text = text.replacen("DEEEEEEEEE?EEEEEEEEEEEEEEEEEEEEEEEEEEEEF", "DEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEEF", 2);
+
+    let assets = PatchedAssets::new().with("levels/0.level", Some(&text));
+    assert_eq!(messages(&validate_level(&assets, &entity_loader, "0")), vec!["levels/0.level:10:11: error: unknown entity '?'"]);
+}
+
+#[test]
+fn items_rows_may_be_short() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+    let terrain = fs::read(assets_dir().join("levels/0.lvl")).unwrap();
+    let items = b"\n  P\n\n    T";
+
+    let items_layer = Layer { file: "0.level", first_line: 40, bytes: items, short_rows: true, width: Level::WIDTH, height: Level::HEIGHT };
+    let terrain_layer = Layer::new_from_file("0.level", &terrain);
+    assert_eq!(messages(&validate_layers(&entity_loader, &terrain_layer, &items_layer)), Vec::<String>::new());
+
+    let level = Level::new_from_layers(&entity_loader, &terrain_layer, &items_layer).unwrap();
+    assert_eq!((level.player.sprite.pos.x(), level.player.sprite.pos.y()), (32, 16));
+    assert_eq!((level.treasure.sprite.pos.x(), level.treasure.sprite.pos.y()), (64, 48));
+}
+
+#[test]
+fn header_problems() {
+    let body = "\n[terrain]\n[items]\n";
+    let parse = |text: &str| match level_file::parse("test.level", text.as_bytes()) {
+        Ok(level_file) => messages(&level_file.warnings),
+        Err(diagnostics) => messages(&diagnostics),
+    };
+
+    assert_eq!(parse("Some level\nwidth: 40\n"), vec!["test.level:1:1: error: expected \"MaskOS level\" as the first line"]);
+    assert_eq!(
+        parse(&format!("MaskOS level\nwidth: forty\n{}", body)),
+        vec!["test.level:2:1: error: width has to be a positive number", "test.level: error: the header has no width", "test.level: error: the header has no height"]
+    );
+    assert_eq!(parse(&format!("MaskOS level\nwidth: 20\nheight: 300\n{}", body)), Vec::<String>::new());
+    assert_eq!(parse("MaskOS level\nwidth: 40\nheight: 30\n[terrain]\n"), vec!["test.level: error: layer [items] is missing"]);
+    assert_eq!(parse(&format!("MaskOS level\nwidth: 40\nheight: 30\n{}[decals]\n", body)), vec!["test.level:7:2: warning: unknown layer [decals] is ignored"]);
+
+    let text = format!("MaskOS level\n# comment\nwidth: 40\nheight: 30\nauthor: Maunz\n{}", body);
+    let level_file = level_file::parse("test.level", text.as_bytes()).ok().unwrap();
+    assert_eq!(level_file.metadata("author"), Some("Maunz"));
+    assert_eq!(level_file.layer("items").unwrap().first_line, 9);
+    assert_eq!(level_file.inventory_size, 1);
+
+    assert_eq!(parse(&format!("MaskOS level\nwidth: 40\nheight: 30\ninventory: 0\n{}", body)), vec!["test.level:4:1: error: inventory has to be a positive number"]);
+    let text = format!("MaskOS level\nwidth: 40\nheight: 30\ninventory: 3\n{}", body);
+    assert_eq!(level_file::parse("test.level", text.as_bytes()).ok().unwrap().inventory_size, 3);
+}
+
+// A walled width x height room with the player and the treasure in opposite corners.
+fn large_level(width: usize, height: usize) -> String {
+    let mut text = format!("MaskOS level\nwidth: {}\nheight: {}\n\n[terrain]\n", width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            text.push(if border { 'B' } else { 'E' });
+        }
+        text.push('\n');
+    }
+    text += "[items]\n\n P\n";
+    for _ in 2..height - 2 {
+        text.push('\n');
+    }
+    text += &format!("{}T\n", " ".repeat(width - 2));
+    text
+}
+
+#[test]
+fn camera_follows_the_player_and_stops_at_the_edges() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+    let tile_set = TileSet::new_from_file(&file_loader, "TileSet.bmp").unwrap();
+    let assets = PatchedAssets::new().with("levels/big.level", Some(&large_level(100, 50)));
+    assert!(!has_errors(&validate_level(&assets, &entity_loader, "big")));
+
+    let mut level = Level::new_from_name(&assets, &entity_loader, "big").unwrap();
+    assert_eq!((level.sprite.tiles_width(), level.sprite.tiles_height()), (100, 50));
+    let camera_pos = |level: &Level| {
+        let camera = Camera::new_following(level, 640, 480);
+        (camera.pos.x(), camera.pos.y())
+    };
+
+    // Top left corner, the camera can't go further up or left.
+    assert_eq!(camera_pos(&level), (0, 0));
+
+    // In the middle the player is in the middle of the screen.
+    level.player.sprite.pos = Vec2::new(50 * 16, 25 * 16);
+    assert_eq!(camera_pos(&level), (50 * 16 + 8 - 320, 25 * 16 + 8 - 240));
+
+    // Bottom right corner, the last screen of the level.
+    level.player.sprite.pos = level.treasure.sprite.pos;
+    assert_eq!(camera_pos(&level), (100 * 16 - 640, 50 * 16 - 480));
+
+    // The wall in the bottom right corner of the level ends up in the corner of the screen.
+    let mut vfb = VirtualFrameBuffer::new();
+    level.draw(&tile_set, &mut vfb);
+    let wall = entity_loader.get("B").unwrap();
+    let wall_pixel = tile_set.tiles[wall.tile_x as usize][wall.tile_y as usize].pixels[15][15];
+    assert_eq!(vfb.pixel(639, 479), wall_pixel);
+}
+
+#[test]
+fn small_levels_are_centered() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+    let assets = PatchedAssets::new().with("levels/small.level", Some(&large_level(20, 10)));
+
+    let level = Level::new_from_name(&assets, &entity_loader, "small").unwrap();
+    let camera = Camera::new_following(&level, 640, 480);
+    assert_eq!((camera.pos.x(), camera.pos.y()), (-(640 - 20 * 16) / 2, -(480 - 10 * 16) / 2));
+}
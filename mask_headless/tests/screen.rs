@@ -0,0 +1,53 @@
+use mask_core::graphics::VirtualFrameBuffer;
+use mask_core::math::Color4;
+use mask_core::screen::{Letterbox, PixelMasks};
+
+const WIDTH: usize = VirtualFrameBuffer::WIDTH;
+const HEIGHT: usize = VirtualFrameBuffer::HEIGHT;
+
+#[test]
+fn scene_is_scaled_by_whole_numbers_and_centered() {
+    let letterbox = |width, height| Letterbox::new(width, height, WIDTH, HEIGHT).map(|l| (l.scale, l.offset_x, l.offset_y));
+
+    assert_eq!(letterbox(640, 480), Some((1, 0, 0)));
+    assert_eq!(letterbox(800, 600), Some((1, 80, 60)));
+    assert_eq!(letterbox(1280, 960), Some((2, 0, 0)));
+    assert_eq!(letterbox(1920, 1080), Some((2, 320, 60)));
+    assert_eq!(letterbox(3840, 2160), Some((4, 640, 120)));
+    assert_eq!(letterbox(320, 240), None);
+    assert_eq!(letterbox(1024, 400), None);
+}
+
+#[test]
+fn best_mode_has_the_largest_scale_and_the_smallest_bars() {
+    let best = |resolutions: &[(usize, usize)]| Letterbox::best_mode(resolutions, WIDTH, HEIGHT);
+
+    assert_eq!(best(&[(800, 600), (640, 480), (1024, 768)]), Some(1));
+    assert_eq!(best(&[(800, 600), (1920, 1080), (1280, 1024)]), Some(2));
+    assert_eq!(best(&[(1024, 768)]), Some(0));
+    assert_eq!(best(&[(320, 200), (600, 800)]), None);
+    assert_eq!(best(&[]), None);
+}
+
+#[test]
+fn colors_are_packed_into_the_pixel_layout() {
+    let orange = Color4::new(0xff, 0x80, 0x10, 1);
+
+    assert_eq!(PixelMasks::RGB.pack(orange).to_le_bytes(), [0xff, 0x80, 0x10, 0]);
+    assert_eq!(PixelMasks::BGR.pack(orange).to_le_bytes(), [0x10, 0x80, 0xff, 0]);
+
+    // 5-6-5 bits, red at the top
+    let rgb565 = PixelMasks { red: 0xf800, green: 0x07e0, blue: 0x001f };
+    assert_eq!(rgb565.pack(orange), (0x1f << 11) | (0x20 << 5) | 0x02);
+    assert_eq!(rgb565.pack(Color4::new(255, 255, 255, 1)), 0xffff);
+
+    // 10 bits per channel, the value fills the top of each
+    let rgb101010 = PixelMasks { red: 0x3ff0_0000, green: 0x000f_fc00, blue: 0x0000_03ff };
+    assert_eq!(rgb101010.pack(orange), (0x3fc << 20) | (0x200 << 10) | 0x040);
+
+    // The buffer's own layout
+    let pixel = VirtualFrameBuffer::pack(orange);
+    assert_eq!(pixel, 0xff8010);
+    assert_eq!(PixelMasks::RGB.repack(pixel), PixelMasks::RGB.pack(orange));
+    assert_eq!(rgb565.repack(pixel), rgb565.pack(orange));
+}
@@ -0,0 +1,169 @@
+//! Golden-image tests: every level in `assets/levels` is rendered and compared against
+//! `tests/snapshots/<level>.ppm`. Run with `UPDATE_SNAPSHOTS=1` to (re)write the references.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mask_core::game::level_names;
+use mask_core::graphics::{EntityLoader, Level, TileSet, VirtualFrameBuffer};
+use mask_core::math::Color4;
+use mask_headless::file_loader::FileLoader;
+
+mod common;
+use common::file_loader;
+
+const MAX_REPORTED_PIXELS: usize = 10;
+
+struct Image {
+    width: usize,
+    height: usize,
+    rgb: Vec<u8>,
+}
+
+impl Image {
+    fn from_ppm(bytes: &[u8]) -> Result<Self, String> {
+        // Header: "P6", width, height and max value separated by whitespace, then one
+        // whitespace byte before the pixel data.
+        let mut fields = vec![];
+        let mut pos = 0;
+        while fields.len() < 4 {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            let start = pos;
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err("truncated PPM header".to_string());
+            }
+            fields.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+        }
+
+        if fields[0] != "P6" || fields[3] != "255" {
+            return Err(format!("unsupported PPM header {:?}", fields));
+        }
+        let width: usize = fields[1].parse().map_err(|_| "bad PPM width".to_string())?;
+        let height: usize = fields[2].parse().map_err(|_| "bad PPM height".to_string())?;
+        let rgb = bytes[pos + 1..].to_vec();
+        if rgb.len() != width * height * 3 {
+            return Err(format!("expected {} bytes of pixel data, found {}", width * height * 3, rgb.len()));
+        }
+
+        Ok(Image { width, height, rgb })
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.width + x) * 3;
+        [self.rgb[i], self.rgb[i + 1], self.rgb[i + 2]]
+    }
+}
+
+fn snapshot_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots")
+}
+
+fn render_level(file_loader: &FileLoader, entity_loader: &EntityLoader, tile_set: &TileSet, level_name: &str) -> VirtualFrameBuffer {
+    let level = Level::new_from_name(file_loader, entity_loader, level_name).unwrap();
+    let mut vfb = VirtualFrameBuffer::new();
+    vfb.clear(Color4::new(0, 0, 0, 255));
+    level.draw(tile_set, &mut vfb);
+    vfb
+}
+
+/// Returns a report of the differing pixels, or `None` if both images are identical.
+fn diff_report(expected: &Image, actual: &Image) -> Option<String> {
+    if (expected.width, expected.height) != (actual.width, actual.height) {
+        return Some(format!(
+            "size mismatch: expected {}x{}, got {}x{}",
+            expected.width, expected.height, actual.width, actual.height
+        ));
+    }
+
+    let mut differing = vec![];
+    for y in 0..expected.height {
+        for x in 0..expected.width {
+            if expected.pixel(x, y) != actual.pixel(x, y) {
+                differing.push((x, y));
+            }
+        }
+    }
+
+    if differing.is_empty() {
+        return None;
+    }
+
+    let (min_x, max_x) = (differing.iter().map(|p| p.0).min().unwrap(), differing.iter().map(|p| p.0).max().unwrap());
+    let (min_y, max_y) = (differing.iter().map(|p| p.1).min().unwrap(), differing.iter().map(|p| p.1).max().unwrap());
+    let mut report = format!(
+        "{} pixels differ, bounding box ({}, {})..=({}, {}), tiles ({}, {})..=({}, {})\n",
+        differing.len(), min_x, min_y, max_x, max_y, min_x / 16, min_y / 16, max_x / 16, max_y / 16
+    );
+    for &(x, y) in differing.iter().take(MAX_REPORTED_PIXELS) {
+        report += &format!("  ({}, {}): expected {:?}, got {:?}\n", x, y, expected.pixel(x, y), actual.pixel(x, y));
+    }
+    if differing.len() > MAX_REPORTED_PIXELS {
+        report += &format!("  ... and {} more\n", differing.len() - MAX_REPORTED_PIXELS);
+    }
+    Some(report)
+}
+
+/// Expected pixels dimmed, differing pixels in bright red.
+fn diff_image(expected: &Image, actual: &Image) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", expected.width, expected.height).into_bytes();
+    for y in 0..expected.height {
+        for x in 0..expected.width {
+            let pixel = expected.pixel(x, y);
+            if pixel == actual.pixel(x, y) {
+                ppm.extend(pixel.iter().map(|c| c / 4));
+            } else {
+                ppm.extend([255, 0, 0]);
+            }
+        }
+    }
+    ppm
+}
+
+#[test]
+fn levels_match_snapshots() {
+    let file_loader = file_loader();
+    let tile_set = TileSet::new_from_file(&file_loader, "TileSet.bmp").unwrap();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("snapshots");
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let mut failures = vec![];
+    for level_name in level_names(&file_loader).unwrap() {
+        let actual_ppm = render_level(&file_loader, &entity_loader, &tile_set, &level_name).to_ppm();
+        let snapshot_path = snapshot_dir().join(format!("{}.ppm", level_name));
+
+        if update {
+            fs::write(&snapshot_path, &actual_ppm).unwrap();
+            continue;
+        }
+
+        let expected_ppm = match fs::read(&snapshot_path) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                failures.push(format!("level {}: no snapshot at {}, run with UPDATE_SNAPSHOTS=1", level_name, snapshot_path.display()));
+                continue;
+            }
+        };
+        let expected = Image::from_ppm(&expected_ppm).unwrap_or_else(|e| panic!("{}: {}", snapshot_path.display(), e));
+        let actual = Image::from_ppm(&actual_ppm).unwrap();
+
+        if let Some(report) = diff_report(&expected, &actual) {
+            let actual_path = out_dir.join(format!("{}.actual.ppm", level_name));
+            let diff_path = out_dir.join(format!("{}.diff.ppm", level_name));
+            fs::write(&actual_path, &actual_ppm).unwrap();
+            if (expected.width, expected.height) == (actual.width, actual.height) {
+                fs::write(&diff_path, diff_image(&expected, &actual)).unwrap();
+            }
+            failures.push(format!("level {}: {}  actual: {}\n  diff: {}", level_name, report, actual_path.display(), diff_path.display()));
+        }
+    }
+
+    assert!(failures.is_empty(), "snapshot mismatch:\n{}", failures.join("\n"));
+}
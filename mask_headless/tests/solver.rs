@@ -0,0 +1,290 @@
+use mask_core::game::level_names;
+use mask_core::graphics::{EntityLoader, Level, Tile, WornMasks};
+use mask_core::math::Vec2;
+use mask_core::platform::GameKey;
+use mask_core::solver::solve;
+use mask_core::validate::validate_level;
+
+mod common;
+use common::{file_loader, test_level, PatchedAssets};
+
+// Plays the moves with the same Level methods the game loop uses, one pixel at a time.
+fn play(level: &mut Level, entity_loader: &EntityLoader, moves: &[GameKey]) -> bool {
+    for key in moves {
+        let move_dir = match key {
+            GameKey::Left => Vec2::new(-1, 0),
+            GameKey::Right => Vec2::new(1, 0),
+            GameKey::Up => Vec2::new(0, -1),
+            GameKey::Down => Vec2::new(0, 1),
+            GameKey::Action => {
+                level.swap_mask(entity_loader);
+                continue;
+            }
+            GameKey::Cycle => {
+                level.player.cycle_mask();
+                continue;
+            }
+        };
+
+        let target = level.player.sprite.pos + move_dir * Tile::WIDTH as i32;
+        for _ in 0..Tile::WIDTH {
+            level.move_player(move_dir);
+            if level.is_solved() {
+                return true;
+            }
+        }
+        assert_eq!(level.player.sprite.pos.x(), target.x(), "blocked while moving {:?}", key);
+        assert_eq!(level.player.sprite.pos.y(), target.y(), "blocked while moving {:?}", key);
+    }
+
+    level.is_solved()
+}
+
+#[test]
+fn all_levels_are_solvable() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+
+    for level_name in level_names(&file_loader).unwrap() {
+        let mut level = Level::new_from_name(&file_loader, &entity_loader, &level_name).unwrap();
+        let moves = solve(&level).unwrap_or_else(|| panic!("level {} is unsolvable", level_name));
+        assert!(play(&mut level, &entity_loader, &moves), "level {}: solution does not reach the treasure", level_name);
+    }
+}
+
+// Both masks have to be carried through the red door, the green one and another red one.
+const THREE_DOORS: &str = "\
+MaskOS level
+width: 12
+height: 3
+{inventory}
+[terrain]
+BBBBBBBBBBBB
+DEEEEvEoEvEF
+HHHHHHHHHHHH
+[items]
+
+ PGR      T
+";
+
+#[test]
+fn carried_masks_are_cycled() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+
+    let assets = test_level("free", &THREE_DOORS.replace("{inventory}", "inventory: 2"));
+    let mut level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert_eq!(level.player.inventory_size, 2);
+    let moves = solve(&level).unwrap();
+    assert_eq!(moves.len(), 13);
+    assert!(moves.contains(&GameKey::Cycle), "{:?}", moves);
+    assert!(play(&mut level, &entity_loader, &moves));
+
+    // Wearing one mask at a time the green mask can't get past the red door.
+    let assets = test_level("free", &THREE_DOORS.replace("{inventory}", ""));
+    let level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert!(solve(&level).is_none());
+}
+
+// The violet door behind a tile that is a mixer ("x") or not.
+const MIXER: &str = "\
+MaskOS level
+width: 9
+height: 3
+
+[terrain]
+BBBBBBBBB
+DE{mixer}EeEEEF
+HHHHHHHHH
+[items]
+
+ PBR   T
+";
+
+#[test]
+fn masks_are_mixed_on_mixers() {
+    let assets = test_level("free", &MIXER.replace("{mixer}", "x"));
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let mut level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    let moves = solve(&level).unwrap();
+    // Take the red mask, then mix it with the blue one lying on the mixer.
+    assert_eq!(moves.len(), 10, "{:?}", moves);
+    assert!(play(&mut level, &entity_loader, &moves));
+    assert_eq!(level.player.worn_mask(), Some(3));
+    assert!(level.masks.is_empty());
+
+    let assets = test_level("free", &MIXER.replace("{mixer}", "E"));
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert!(solve(&level).is_none());
+}
+
+// Doors for carrying red and blue, for not wearing red and for no mask at all.
+const RULE_DOORS: &str = r#"
+  "q": { "name": "Red and blue door", "tile_x": 27, "tile_y": 3, "wall": true, "door_colors": [0, 2], "door_rule": "all" },
+  "k": { "name": "Not red door", "tile_x": 27, "tile_y": 4, "wall": true, "door_colors": [0], "door_rule": "not" },
+  "j": { "name": "Barrier", "tile_x": 27, "tile_y": 5, "wall": true, "door_rule": "bare" }"#;
+
+#[test]
+fn door_rules_decide_who_passes() {
+    let entity_loader = EntityLoader::new(&PatchedAssets::new().with_entities(RULE_DOORS)).unwrap();
+    let masks = |worn: Option<usize>, carried: &[usize]| WornMasks { worn, carried: carried.to_vec(), plates_pressed: false };
+    let opens = |id: &str, masks: &WornMasks| entity_loader.get(id).unwrap().opens_for(masks);
+
+    // Red/Blue door, any of the two
+    assert!(opens("V", &masks(Some(2), &[1, 2])));
+    assert!(!opens("V", &masks(Some(1), &[1, 2])));
+    assert!(!opens("V", &masks(None, &[])));
+
+    assert!(opens("q", &masks(Some(1), &[0, 1, 2])));
+    assert!(!opens("q", &masks(Some(0), &[0])));
+
+    assert!(opens("k", &masks(Some(2), &[0, 2])));
+    assert!(opens("k", &masks(None, &[])));
+    assert!(!opens("k", &masks(Some(0), &[0, 2])));
+
+    assert!(opens("j", &masks(None, &[])));
+    assert!(!opens("j", &masks(Some(1), &[1])));
+
+    // Plain walls and floor don't care.
+    assert!(!opens("B", &masks(Some(0), &[0])));
+    assert!(opens("E", &masks(Some(0), &[0])));
+}
+
+#[test]
+fn doors_needing_all_masks_list_the_missing_ones() {
+    let level = "MaskOS level\nwidth: 6\nheight: 3\n\n[terrain]\nBBBBBB\nDEEqEF\nHHHHHH\n[items]\n\n PG T\n";
+    let assets = test_level("free", level).with_entities(RULE_DOORS);
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let messages: Vec<String> = validate_level(&assets, &entity_loader, "test").iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, ["levels/test.level:7:4: warning: door 'q' can never be opened, it needs red mask and blue mask, which are not in this level"]);
+}
+
+#[test]
+fn masks_are_left_behind_for_barriers() {
+    let level = "\
+MaskOS level
+width: 12
+height: 3
+inventory: 2
+
+[terrain]
+BBBBBBBBBBBB
+DEEEqEEjEkEF
+HHHHHHHHHHHH
+[items]
+
+ PRB      T
+";
+    let assets = test_level("free", level).with_entities(RULE_DOORS);
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let mut level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    let moves = solve(&level).unwrap();
+    // Take both masks for the first door, drop them on two tiles for the barrier.
+    assert_eq!(moves.len(), 13, "{:?}", moves);
+    assert!(play(&mut level, &entity_loader, &moves));
+    assert_eq!(level.masks.len(), 2);
+}
+
+// A wall that is a passage for the red mask.
+const SECRET_PASSAGE: &str = r#"
+  "h": { "name": "Secret passage", "tile_x": 16, "tile_y": 1, "wall": true, "revealed_by": [0], "revealed": "E" }"#;
+
+#[test]
+fn hidden_tiles_are_revealed_by_masks() {
+    let level = "\
+MaskOS level
+width: 8
+height: 3
+
+[terrain]
+BBBBBBBB
+DEEEhEEF
+HHHHHHHH
+[items]
+
+ P{mask}   T
+";
+    let assets = test_level("free", &level.replace("{mask}", "R")).with_entities(SECRET_PASSAGE);
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let mut level_with_mask = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    let moves = solve(&level_with_mask).unwrap();
+    assert_eq!(moves.len(), 6, "{:?}", moves);
+    assert!(play(&mut level_with_mask, &entity_loader, &moves));
+
+    // A green mask shows the wall.
+    let assets = test_level("free", &level.replace("{mask}", "G")).with_entities(SECRET_PASSAGE);
+    let level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert!(solve(&level).is_none());
+}
+
+// A room with a plate and a door that opens while a crate is on it, of the shipped entities.
+const PLATE_ROOM: &str = "\
+MaskOS level
+width: 10
+height: 5
+
+[terrain]
+BBBBBBBBBB
+DEEE_EHEEF
+DEEEEE=EEF
+DEEEEEHEEF
+HHHHHHHHHH
+[items]
+
+{crates}
+        T
+{corner}
+";
+
+#[test]
+fn crates_are_pushed_onto_plates() {
+    let assets = test_level("free", &PLATE_ROOM.replace("{crates}", " PC").replace("{corner}", ""));
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let mut level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert_eq!(level.crates.len(), 1);
+    assert!(!level.plates_pressed());
+
+    let moves = solve(&level).unwrap();
+    // Two pushes onto the plate, then down and through the door.
+    assert_eq!(moves.len(), 8, "{:?}", moves);
+    assert!(play(&mut level, &entity_loader, &moves));
+    assert!(level.plates_pressed());
+    assert_eq!(level.crates[0].sprite.pos.x(), 4 * Tile::WIDTH as i32);
+}
+
+#[test]
+fn crates_block_like_walls() {
+    // A crate in a corner can't be pushed anywhere.
+    let assets = test_level("free", &PLATE_ROOM.replace("{crates}", " P").replace("{corner}", " C"));
+    let entity_loader = EntityLoader::new(&assets).unwrap();
+    let level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert!(solve(&level).is_none());
+
+    // Nor onto the treasure, which is behind it in this corridor.
+    let corridor = "MaskOS level\nwidth: 7\nheight: 3\n\n[terrain]\nBBBBBBB\nDEEEEEF\nHHHHHHH\n[items]\n\n P C T\n";
+    let assets = test_level("free", corridor);
+    let mut level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert!(solve(&level).is_none());
+    assert!(!play(&mut level, &entity_loader, &[GameKey::Right, GameKey::Right]));
+    assert_eq!(level.crates[0].sprite.pos.x(), 4 * Tile::WIDTH as i32);
+    let start = level.player.sprite.pos;
+    level.move_player(Vec2::new(1, 0));
+    assert_eq!(level.player.sprite.pos.x(), start.x());
+
+    // Without plates to press the door never opens.
+    let assets = test_level("free", &PLATE_ROOM.replace('_', "E").replace("{crates}", " PC").replace("{corner}", ""));
+    let level = Level::new_from_name(&assets, &entity_loader, "test").unwrap();
+    assert_eq!(level.plates, 0);
+    assert!(!level.plates_pressed());
+    assert!(solve(&level).is_none());
+    let diagnostics = validate_level(&assets, &entity_loader, "test");
+    let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, ["levels/test.level:8:7: warning: door '=' can never be opened, there are no plates in this level"]);
+
+    // Without a crate for the plate the door stays shut.
+    let assets = test_level("free", &PLATE_ROOM.replace("{crates}", " P").replace("{corner}", ""));
+    let diagnostics = validate_level(&assets, &entity_loader, "test");
+    let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, ["levels/test.level:8:7: warning: door '=' can never be opened, it needs 1 crate for 1 plate, the level has 0 crates"]);
+}
@@ -0,0 +1,103 @@
+use mask_core::game::level_names;
+use mask_core::graphics::{EntityLoader, Level};
+use mask_core::validate::{has_errors, validate_level, validate_level_bytes, Diagnostic, Severity};
+
+mod common;
+use common::file_loader;
+
+// A walled room with the given items placed inside.
+fn room(items: &[(usize, usize, char)]) -> (String, String) {
+    let mut level = String::new();
+    let mut level_items = String::new();
+    for y in 0..Level::HEIGHT {
+        for x in 0..Level::WIDTH {
+            let border = x == 0 || y == 0 || x == Level::WIDTH - 1 || y == Level::HEIGHT - 1;
+            level.push(if border { 'B' } else { 'E' });
+            level_items.push(items.iter().find(|(ix, iy, _)| (*ix, *iy) == (x, y)).map_or(' ', |item| item.2));
+        }
+        level.push('\n');
+        level_items.push('\n');
+    }
+    (level, level_items)
+}
+
+fn validate(level: &str, items: &str) -> Vec<Diagnostic> {
+    let entity_loader = EntityLoader::new(&file_loader()).unwrap();
+    validate_level_bytes(&entity_loader, "test.lvl", level.as_bytes(), "test.lvl.items", items.as_bytes())
+}
+
+fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+    diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect()
+}
+
+#[test]
+fn shipped_levels_are_valid() {
+    let file_loader = file_loader();
+    let entity_loader = EntityLoader::new(&file_loader).unwrap();
+    for level_name in level_names(&file_loader).unwrap() {
+        let diagnostics = validate_level(&file_loader, &entity_loader, &level_name);
+        assert!(!has_errors(&diagnostics), "{:#?}", messages(&diagnostics));
+    }
+}
+
+#[test]
+fn valid_room_has_no_diagnostics() {
+    let (level, items) = room(&[(2, 2, 'P'), (5, 5, 'T'), (7, 7, 'R')]);
+    assert_eq!(messages(&validate(&level, &items)), Vec::<String>::new());
+}
+
+#[test]
+fn reports_unknown_entity_with_position() {
+    let (mut level, items) = room(&[(2, 2, 'P'), (5, 5, 'T')]);
+    // line 4, column 11
+    level.replace_range(3 * (Level::WIDTH + 1) + 10..3 * (Level::WIDTH + 1) + 11, "?");
+    assert_eq!(messages(&validate(&level, &items)), vec!["test.lvl:4:11: error: unknown entity '?'"]);
+}
+
+#[test]
+fn reports_missing_and_duplicate_player_and_treasure() {
+    let (level, items) = room(&[(2, 2, 'P'), (3, 2, 'P')]);
+    assert_eq!(
+        messages(&validate(&level, &items)),
+        vec![
+            "test.lvl.items:3:4: error: more than one player 'P', the first one is at 3:3",
+            "test.lvl.items: error: no treasure 'T'",
+        ]
+    );
+}
+
+#[test]
+fn warns_about_unknown_items() {
+    let (level, items) = room(&[(2, 2, 'P'), (5, 5, 'T'), (9, 4, 'x')]);
+    let diagnostics = validate(&level, &items);
+    assert_eq!(messages(&diagnostics), vec!["test.lvl.items:5:10: warning: unknown item 'x' is ignored"]);
+    assert!(!has_errors(&diagnostics));
+}
+
+#[test]
+fn reports_items_in_walls() {
+    let (level, items) = room(&[(2, 2, 'P'), (5, 5, 'T'), (0, 7, 'G')]);
+    assert_eq!(messages(&validate(&level, &items)), vec!["test.lvl.items:8:1: error: mask 'G' is placed on wall 'B'"]);
+}
+
+#[test]
+fn reports_line_shape_problems() {
+    let (level, items) = room(&[(2, 2, 'P'), (5, 5, 'T')]);
+    let crlf = level.replace('\n', "\r\n");
+    assert_eq!(messages(&validate(&crlf, &items)), vec!["test.lvl:1:41: error: CRLF line ending, only LF is supported"]);
+
+    let short_line = level.replacen("EB\n", "B\n", 1);
+    assert_eq!(messages(&validate(&short_line, &items)), vec!["test.lvl:2:40: error: line has 39 columns, expected 40"]);
+
+    let missing_lines: String = level.lines().take(28).map(|line| format!("{}\n", line)).collect();
+    assert_eq!(messages(&validate(&missing_lines, &items)), vec!["test.lvl:29:1: error: file has 28 lines, expected 30"]);
+}
+
+#[test]
+fn warns_about_doors_without_matching_mask() {
+    let (level, items) = room(&[(2, 2, 'P'), (5, 5, 'T'), (7, 7, 'G')]);
+    let level = level.replacen("BBBB", "BBYB", 1);
+    let diagnostics = validate(&level, &items);
+    assert_eq!(messages(&diagnostics), vec!["test.lvl:1:3: warning: door 'Y' can never be opened, there is no red mask or blue mask in this level"]);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+}
@@ -0,0 +1,33 @@
+use core::arch::x86_64::_rdtsc;
+
+use uefi::table::boot::BootServices;
+
+use mask_core::platform::Clock;
+
+/*
+ * The time stamp counter, calibrated once against BootServices::stall. It counts at a
+ * constant rate on every x86_64 CPU from the last decade, whatever the current clock speed.
+ */
+pub struct TscClock {
+    ticks_per_micro: u64,
+}
+
+impl TscClock {
+    const CALIBRATION_MICROS: usize = 20_000;
+
+    pub fn new(boot_services: &BootServices) -> Self {
+        let start = unsafe { _rdtsc() };
+        boot_services.stall(Self::CALIBRATION_MICROS);
+        let end = unsafe { _rdtsc() };
+
+        TscClock {
+            ticks_per_micro: ((end - start) / Self::CALIBRATION_MICROS as u64).max(1),
+        }
+    }
+}
+
+impl Clock for TscClock {
+    fn now_micros(&self) -> u64 {
+        unsafe { _rdtsc() / self.ticks_per_micro }
+    }
+}
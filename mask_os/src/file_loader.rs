@@ -0,0 +1,201 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use uefi::{CString16, Handle, Status};
+use uefi::data_types::Align;
+use uefi::proto::loaded_image;
+use uefi::proto::media::{file, fs};
+use uefi::proto::media::file::{File, FileAttribute, FileInfo, FileMode, RegularFile};
+use uefi::table::boot::{BootServices, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol};
+
+use mask_core::error::AssetError;
+use mask_core::platform::AssetSource;
+
+// read_file keeps whole files in memory, anything bigger has to be streamed with open_file.
+const MAX_FILE_SIZE: usize = 16 * 1024 * 1024;
+
+/*
+ * Opens the volume MaskOS was booted from once and keeps it open: the root directory
+ * and the SimpleFileSystem protocol are released when the FileLoader is dropped.
+ * Paths are relative to the root and may be nested, "levels/3.lvl".
+ */
+pub struct FileLoader<'a> {
+    // Declared before the protocol, fields are dropped in this order.
+    root: RefCell<file::Directory>,
+    _file_system: ScopedProtocol<'a, fs::SimpleFileSystem>,
+}
+
+/*
+ * A file opened for reading. The size comes from the file's FileInfo, read returns
+ * the next bytes of the file and 0 at its end.
+ */
+pub struct FileReader {
+    file: RegularFile,
+    path: String,
+    size: usize,
+    position: usize,
+}
+
+impl FileReader {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, AssetError> {
+        let bytes_read = self.file.read(buf)
+            .map_err(|_| AssetError::Io { file: self.path.clone(), message: "could not read file" })?;
+        self.position += bytes_read;
+        Ok(bytes_read)
+    }
+
+    // Reads the rest of the file into a buffer allocated for the size from FileInfo.
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>, AssetError> {
+        let mut data = vec![0; self.size - self.position.min(self.size)];
+        let mut filled = 0;
+        while filled < data.len() {
+            // Firmware may return less than asked for, and a file may shrink while it is read.
+            let bytes_read = self.read(&mut data[filled..])?;
+            if bytes_read == 0 {
+                break;
+            }
+            filled += bytes_read;
+        }
+        data.truncate(filled);
+        Ok(data)
+    }
+}
+
+impl<'a> FileLoader<'a> {
+    pub fn new(image: Handle, boot_services: &'a BootServices) -> Result<Self, AssetError> {
+        let volume_error = |message| AssetError::Io { file: String::from("/"), message };
+
+        // Only needed to find the device, so it is closed again right away.
+        let device = boot_services
+            .open_protocol_exclusive::<loaded_image::LoadedImage>(image)
+            .map_err(|_| volume_error("could not open the loaded image"))?
+            .device();
+
+        // Not exclusive, the firmware and other drivers keep their access to the volume.
+        let mut file_system = unsafe {
+            boot_services.open_protocol::<fs::SimpleFileSystem>(
+                OpenProtocolParams {
+                    handle: device,
+                    agent: image,
+                    controller: None,
+                },
+                OpenProtocolAttributes::GetProtocol,
+            )
+        }
+        .map_err(|_| volume_error("could not open the boot volume"))?;
+        let root = file_system.open_volume().map_err(|_| volume_error("could not open the boot volume"))?;
+
+        Ok(FileLoader {
+            root: RefCell::new(root),
+            _file_system: file_system,
+        })
+    }
+
+    // UEFI separates directories with backslashes.
+    fn open(&self, path: &str, mode: FileMode, attributes: FileAttribute) -> Result<file::FileHandle, AssetError> {
+        let uefi_path = CString16::try_from(path.replace('/', "\\").as_str())
+            .map_err(|_| AssetError::Io { file: String::from(path), message: "path can't be encoded in UCS-2" })?;
+        self.root.borrow_mut().open(&uefi_path, mode, attributes)
+            .map_err(|e| open_error(path, e.status()))
+    }
+
+    // The names of the files in a directory, without its subdirectories.
+    pub fn list_directory(&self, directory: &str) -> Result<Vec<String>, AssetError> {
+        let mut dir = self.open(directory, FileMode::Read, FileAttribute::READ_ONLY)?
+            .into_directory()
+            .ok_or_else(|| AssetError::NotFound { file: String::from(directory) })?;
+        let read_error = || AssetError::Io { file: String::from(directory), message: "could not read directory" };
+
+        let mut names = vec![];
+        let mut buf = vec![0u8; 256];
+        loop {
+            let aligned = FileInfo::align_buf(&mut buf).ok_or_else(read_error)?;
+            match dir.read_entry(aligned) {
+                Ok(Some(info)) => {
+                    if !info.attribute().contains(FileAttribute::DIRECTORY) {
+                        names.push(format!("{}", info.file_name()));
+                    }
+                }
+                Ok(None) => break,
+                // The buffer was too small for this entry's name.
+                Err(e) => match *e.data() {
+                    Some(size) => buf = vec![0u8; size + FileInfo::alignment()],
+                    None => return Err(read_error()),
+                },
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn open_file(&self, file_name: &str, directory: Option<&str>) -> Result<FileReader, AssetError> {
+        let path = AssetError::path(file_name, directory);
+        let mut file = self.open(&path, FileMode::Read, FileAttribute::READ_ONLY)?
+            .into_regular_file()
+            .ok_or_else(|| AssetError::NotARegularFile { file: path.clone() })?;
+
+        let info = file.get_boxed_info::<FileInfo>()
+            .map_err(|_| AssetError::Io { file: path.clone(), message: "could not get file info" })?;
+        let size = usize::try_from(info.file_size())
+            .map_err(|_| AssetError::TooLarge { file: path.clone(), max_size: usize::MAX })?;
+
+        Ok(FileReader { file, path, size, position: 0 })
+    }
+
+    pub fn read_file(&self, file_name: &str, directory: Option<&str>) -> Result<Vec<u8>, AssetError> {
+        let mut reader = self.open_file(file_name, directory)?;
+        if reader.size() > MAX_FILE_SIZE {
+            return Err(AssetError::TooLarge { file: reader.path, max_size: MAX_FILE_SIZE });
+        }
+        reader.read_to_end()
+    }
+
+    pub fn write_file(&self, file_name: &str, directory: Option<&str>, data: &[u8]) -> Result<(), AssetError> {
+        let path = AssetError::path(file_name, directory);
+
+        // Delete an existing file first, a shorter write would leave its tail behind.
+        if let Ok(handle) = self.open(&path, FileMode::ReadWrite, FileAttribute::empty()) {
+            handle.delete().map_err(|_| AssetError::Io { file: path.clone(), message: "could not replace file" })?;
+        }
+
+        let handle = self.open(&path, FileMode::CreateReadWrite, FileAttribute::empty())?;
+        let mut regular = handle.into_regular_file().ok_or_else(|| AssetError::NotARegularFile { file: path.clone() })?;
+        regular.write(data).map_err(|_| AssetError::Io { file: path.clone(), message: "could not write file" })?;
+        regular.flush().map_err(|_| AssetError::Io { file: path, message: "could not flush file" })
+    }
+}
+
+// Only a file the firmware can't find is missing, anything else is an error reading the volume.
+fn open_error(path: &str, status: Status) -> AssetError {
+    let message = match status {
+        Status::NOT_FOUND => return AssetError::NotFound { file: String::from(path) },
+        Status::ACCESS_DENIED => "access denied",
+        Status::WRITE_PROTECTED => "volume is write protected",
+        Status::VOLUME_FULL => "volume is full",
+        Status::VOLUME_CORRUPTED => "volume is corrupted",
+        Status::NO_MEDIA | Status::MEDIA_CHANGED => "medium was removed or changed",
+        Status::DEVICE_ERROR => "device error",
+        Status::OUT_OF_RESOURCES => "out of resources",
+        _ => "could not open file",
+    };
+    AssetError::Io { file: String::from(path), message }
+}
+
+impl<'a> AssetSource for FileLoader<'a> {
+    fn read_file(&self, file_name: &str, directory: Option<&str>) -> Result<Vec<u8>, AssetError> {
+        FileLoader::read_file(self, file_name, directory)
+    }
+
+    fn list_directory(&self, directory: &str) -> Result<Vec<String>, AssetError> {
+        FileLoader::list_directory(self, directory)
+    }
+}
+
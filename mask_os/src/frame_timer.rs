@@ -0,0 +1,41 @@
+use uefi::Event;
+use uefi::table::boot::{BootServices, EventType, TimerTrigger, Tpl};
+
+/*
+ * Paces the game loop with a periodic UEFI timer event: wait returns once per frame, so
+ * frames are rendered at FRAMES_PER_SECOND instead of as fast as the firmware blits. If
+ * a frame takes longer than that, the event is already signaled and wait returns right
+ * away. How far the game gets in a frame is up to game::StepTimer.
+ */
+pub struct FrameTimer<'a> {
+    boot_services: &'a BootServices,
+    event: Event,
+}
+
+impl<'a> FrameTimer<'a> {
+    pub const FRAMES_PER_SECOND: u64 = 100;
+
+    pub fn new(boot_services: &'a BootServices) -> uefi::Result<Self> {
+        let event = unsafe { boot_services.create_event(EventType::TIMER, Tpl::APPLICATION, None, None)? };
+        // In units of 100ns
+        boot_services.set_timer(&event, TimerTrigger::Periodic(10_000_000 / Self::FRAMES_PER_SECOND))?;
+
+        Ok(FrameTimer {
+            boot_services,
+            event,
+        })
+    }
+
+    // Blocks until the next frame starts.
+    pub fn wait(&mut self) {
+        let mut events = [unsafe { self.event.unsafe_clone() }];
+        self.boot_services.wait_for_event(&mut events).expect("failed to wait for the frame timer");
+    }
+}
+
+impl<'a> Drop for FrameTimer<'a> {
+    fn drop(&mut self) {
+        let _ = self.boot_services.set_timer(&self.event, TimerTrigger::Cancel);
+        let _ = self.boot_services.close_event(unsafe { self.event.unsafe_clone() });
+    }
+}
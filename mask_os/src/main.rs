@@ -0,0 +1,323 @@
+#![no_main]
+#![no_std]
+#![feature(abi_efiapi)]
+#![feature(lang_items)]
+
+#[macro_use]
+extern crate alloc;
+
+pub mod clock;
+pub mod file_loader;
+pub mod frame_timer;
+pub mod platform;
+
+use uefi::prelude::*;
+use uefi_services::{print, println};
+use uefi::table::boot::{OpenProtocolAttributes, OpenProtocolParams};
+use uefi::proto::console::gop::GraphicsOutput;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use mask_core::demo::{Demo, RecordingInput, ReplayInput};
+use mask_core::error::AssetError;
+use mask_core::game::{Game, StepTimer};
+use mask_core::graphics::{EntityLoader, LevelPack, VirtualFrameBuffer};
+use mask_core::platform::Input;
+use mask_core::screen::Letterbox;
+use mask_core::validate::{has_errors, validate_level};
+use crate::clock::TscClock;
+use crate::file_loader::FileLoader;
+use crate::frame_timer::FrameTimer;
+use crate::platform::{UefiDisplay, UefiInput};
+
+const RECORD_FILE: &str = "record.demo";
+const REPLAY_FILE: &str = "replay.demo";
+
+#[entry]
+unsafe fn main(image: Handle, mut st: SystemTable<Boot>) -> Status {
+    uefi_services::init(&mut st).expect("failed to init uefi services");
+
+    let st_clone = st.unsafe_clone();
+    let bt = st_clone.boot_services();
+
+    let file_loader = match FileLoader::new(image, bt) {
+        Ok(file_loader) => file_loader,
+        Err(e) => return show_error_page(&mut st, bt, &e),
+    };
+    match levels_are_valid(&file_loader) {
+        Ok(true) => {}
+        Ok(false) => {
+            println!("Please fix the levels above. Press any key to exit.");
+            wait_for_any_key(&mut st, bt);
+            return Status::LOAD_ERROR;
+        }
+        Err(e) => return show_error_page(&mut st, bt, &e),
+    }
+
+    if let Ok(handle) = bt.get_handle_for_protocol::<GraphicsOutput>() {
+        let gop = &mut bt
+            .open_protocol::<GraphicsOutput>(
+                OpenProtocolParams {
+                    handle,
+                    agent: image,
+                    controller: None,
+                },
+                // For this character, don't open in exclusive mode. That
+                // would break the connection between stdout and the
+                // video console.
+                OpenProtocolAttributes::GetProtocol,
+            )
+            .expect("failed to open Graphics Output Protocol");
+
+        let intro = "Welcome to the magical mansion of Maunz!\n\
+            \n\
+            You have been too curious and now you are trapped!\n\
+            (insert mad laughter here)\n\
+            \n\
+            The only way out is to solve my wonderful puzzles,\n\
+            but don't believe they are easy!\n\
+            \n\
+            Mysterious masks might help you walking through\n\
+            some doors in this mansion.\n\
+            You can take or drop them by pressing SPACE.\n\
+            \n\
+            Now find your way out with the ARROW keys\n\
+            I will watch you.\n\
+            (insert more laughter here, because there simply is\n\
+            no sound output available)\n";
+
+        for character in intro.chars() {
+            print!("{}", character);
+            bt.stall(50000);
+        }
+
+        for n in (1..4).rev() {
+            print!("{}", n);
+            bt.stall(500000);
+
+            for _ in 0..3 {
+                print!(".");
+                bt.stall(500000);
+            }
+
+            print!(" ");
+            bt.stall(500000);
+        }
+
+        let letterbox = match choose_graphics_mode(gop) {
+            Some(letterbox) => letterbox,
+            None => {
+                println!(
+                    "No graphics mode of at least {}x{} is available. Press any key to exit.",
+                    VirtualFrameBuffer::WIDTH,
+                    VirtualFrameBuffer::HEIGHT
+                );
+                wait_for_any_key(&mut st, bt);
+                return Status::UNSUPPORTED;
+            }
+        };
+
+        let mut display = UefiDisplay::new(gop, letterbox);
+
+        let game = match Game::new(&file_loader) {
+            Ok(game) => game,
+            Err(e) => return show_error_page(&mut st, bt, &e),
+        };
+
+        // A demo in REPLAY_FILE is played back instead of reading the keyboard, otherwise
+        // every key press is recorded to RECORD_FILE, so a playthrough can be reproduced.
+        if let Ok(demo_bytes) = file_loader.read_file(REPLAY_FILE, None) {
+            let demo = match parse_demo(&demo_bytes) {
+                Ok(demo) => demo,
+                Err(e) => return show_error_page(&mut st, bt, &e),
+            };
+            run_game(&mut st, bt, &file_loader, &mut display, game, &mut ReplayInput::new(demo), None)
+        } else {
+            let mut input = RecordingInput::new(UefiInput::new(st.unsafe_clone()));
+            run_game(&mut st, bt, &file_loader, &mut display, game, &mut input, Some(RecordingInput::demo))
+        }
+    } else {
+        println!("GOP not supported!");
+        panic!();
+    }
+
+    //Status::SUCCESS
+}
+
+/*
+ * Every frame runs the game steps that are due on the clock, then renders into vfb and
+ * presents it as a whole. With record, the demo it gives is written to RECORD_FILE after
+ * every level, and the level pages wait for a key; a replay doesn't. Returns the status
+ * to exit with.
+ */
+fn run_game<I: Input>(
+    st: &mut SystemTable<Boot>,
+    bt: &BootServices,
+    file_loader: &FileLoader,
+    display: &mut UefiDisplay,
+    mut game: Game,
+    input: &mut I,
+    record: Option<fn(&I) -> &Demo>,
+) -> Status {
+    let clock = TscClock::new(bt);
+    let mut frame_timer = match FrameTimer::new(bt) {
+        Ok(frame_timer) => frame_timer,
+        Err(e) => {
+            println!("Could not create the frame timer: {:?}. Press any key to exit.", e.status());
+            wait_for_any_key(st, bt);
+            return e.status();
+        }
+    };
+    let mut vfb = VirtualFrameBuffer::new();
+    let mut shown_level = usize::MAX;
+    let mut step_timer = StepTimer::new(&clock);
+
+    loop {
+        if game.level_index != shown_level {
+            show_level_page(st, bt, &game, record.is_some());
+            display.text_shown();
+            shown_level = game.level_index;
+            // The time spent on the level page is not played.
+            step_timer.reset(&clock);
+        }
+
+        frame_timer.wait();
+
+        let steps = step_timer.due_steps(&clock);
+        let result = game.run_frame(file_loader, input, display, &mut vfb, steps);
+
+        // The events stay in memory while a level is played, the file is written when it ends.
+        // After an error too, what was played up to it may show how to get there.
+        if let Some(record) = record {
+            if result.is_err() || game.level_index != shown_level || game.escaped {
+                let written = file_loader.write_file(RECORD_FILE, None, record(input).to_text().as_bytes());
+                if let (Ok(()), Err(e)) = (&result, written) {
+                    return show_error_page(st, bt, &e);
+                }
+            }
+        }
+        if let Err(e) = result {
+            return show_error_page(st, bt, &e);
+        }
+        if game.escaped {
+            return show_escaped_screen(st, bt, &game);
+        }
+    }
+}
+
+// Checks all levels on the volume and prints what is wrong with them.
+fn levels_are_valid(file_loader: &FileLoader) -> Result<bool, AssetError> {
+    let mut valid = true;
+
+    for pack_level in LevelPack::new(file_loader)?.levels {
+        let entity_loader = EntityLoader::new_from_file(file_loader, pack_level.entities_file())?;
+        let diagnostics = validate_level(file_loader, &entity_loader, &pack_level.level);
+        for diagnostic in diagnostics.iter() {
+            println!("{}", diagnostic);
+        }
+
+        valid &= !has_errors(&diagnostics);
+    }
+
+    Ok(valid)
+}
+
+fn parse_demo(demo_bytes: &[u8]) -> Result<Demo, AssetError> {
+    let bad_demo = |message: String| AssetError::BadDemo { file: String::from(REPLAY_FILE), message };
+    let demo_text = core::str::from_utf8(demo_bytes).map_err(|_| bad_demo(String::from("demo is not valid UTF-8")))?;
+    Demo::parse(demo_text).map_err(bad_demo)
+}
+
+fn wait_for_any_key(st: &mut SystemTable<Boot>, bt: &BootServices) {
+    // Forget the keys that were pressed while playing.
+    st.stdin().reset(false).ok();
+    let mut events = unsafe { [st.stdin().wait_for_key_event().unsafe_clone()] };
+    bt.wait_for_event(&mut events).ok();
+    // The key is only for this page, the game must not read it as its first move.
+    st.stdin().read_key().ok();
+}
+
+/*
+ * Shown before every level, if the level pack has something to say about it. In a
+ * replay nobody is there to press a key, so the page just stays for a moment.
+ */
+fn show_level_page(st: &mut SystemTable<Boot>, bt: &BootServices, game: &Game, wait: bool) {
+    let pack_level = game.pack_level();
+    if pack_level.name.is_none() && pack_level.intro.is_none() {
+        return;
+    }
+
+    st.stdout().reset(false).ok();
+    println!("Level {} of {}: {}", game.level_index + 1, game.pack.levels.len(), pack_level.display_name());
+    println!();
+    if let Some(intro) = &pack_level.intro {
+        println!("{}", intro);
+        println!();
+    }
+    if let Some(par_moves) = pack_level.par_moves {
+        println!("Can you do it in {} moves?", par_moves);
+        println!();
+    }
+
+    if wait {
+        println!("Press any key to start.");
+        wait_for_any_key(st, bt);
+    } else {
+        bt.stall(2_000_000);
+    }
+}
+
+fn show_escaped_screen(st: &mut SystemTable<Boot>, bt: &BootServices, game: &Game) -> Status {
+    st.stdout().reset(false).ok();
+
+    let outro = "You found the last treasure and the door swings open.\n\
+        \n\
+        You escaped the magical mansion of Maunz!\n\
+        (insert disappointed grumbling here)\n\
+        \n";
+
+    for character in outro.chars() {
+        print!("{}", character);
+        bt.stall(50000);
+    }
+
+    for (pack_level, moves) in game.pack.levels.iter().zip(game.solved_moves.iter()) {
+        match pack_level.par_moves {
+            Some(par_moves) => println!("{}: {} moves (par {})", pack_level.display_name(), moves, par_moves),
+            None => println!("{}: {} moves", pack_level.display_name(), moves),
+        }
+    }
+    println!();
+    println!("Press any key to leave.");
+
+    wait_for_any_key(st, bt);
+    Status::SUCCESS
+}
+
+/*
+ * Replaces the game with a text screen telling what went wrong, instead of panicking
+ * somewhere in the asset loaders. Returns the status to exit with.
+ */
+fn show_error_page(st: &mut SystemTable<Boot>, bt: &BootServices, error: &AssetError) -> Status {
+    st.stdout().reset(false).ok();
+    println!("MaskOS could not load its assets:");
+    println!();
+    println!("{}", error);
+    println!();
+    println!("Press any key to exit.");
+    wait_for_any_key(st, bt);
+    Status::LOAD_ERROR
+}
+
+// Switches to the mode that shows the scene largest, see Letterbox::best_mode.
+fn choose_graphics_mode(gop: &mut GraphicsOutput) -> Option<Letterbox> {
+    let resolutions: Vec<(usize, usize)> = gop.modes().map(|mode| mode.info().resolution()).collect();
+    let mode_index = Letterbox::best_mode(&resolutions, VirtualFrameBuffer::WIDTH, VirtualFrameBuffer::HEIGHT)?;
+
+    let mode = gop.modes().nth(mode_index).unwrap();
+    let (width, height) = mode.info().resolution();
+    gop.set_mode(&mode).expect("failed to set graphics mode");
+
+    Letterbox::new(width, height, VirtualFrameBuffer::WIDTH, VirtualFrameBuffer::HEIGHT)
+}
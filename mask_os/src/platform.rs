@@ -0,0 +1,199 @@
+use uefi::prelude::*;
+use alloc::vec::Vec;
+use uefi::proto::console::gop::{BltOp, BltPixel, BltRegion, FrameBuffer, GraphicsOutput, ModeInfo, PixelFormat};
+use uefi::proto::console::text::{Key, ScanCode};
+
+use mask_core::graphics::{Rect, VirtualFrameBuffer};
+use mask_core::platform::{Display, GameKey, Input};
+use mask_core::screen::{Letterbox, PixelMasks};
+
+pub struct UefiInput {
+    system_table: SystemTable<Boot>,
+}
+
+impl UefiInput {
+    pub fn new(system_table: SystemTable<Boot>) -> Self {
+        UefiInput {
+            system_table,
+        }
+    }
+}
+
+impl Input for UefiInput {
+    fn read_key(&mut self) -> Option<GameKey> {
+        match self.system_table.stdin().read_key().unwrap() {
+            Some(Key::Special(ScanCode::LEFT)) => Some(GameKey::Left),
+            Some(Key::Special(ScanCode::RIGHT)) => Some(GameKey::Right),
+            Some(Key::Special(ScanCode::UP)) => Some(GameKey::Up),
+            Some(Key::Special(ScanCode::DOWN)) => Some(GameKey::Down),
+            Some(Key::Printable(character)) if character == ' '.try_into().unwrap() => Some(GameKey::Action),
+            Some(Key::Printable(character)) if character == '\t'.try_into().unwrap() => Some(GameKey::Cycle),
+            _ => None
+        }
+    }
+}
+
+/*
+ * Shows the buffer through the GOP: written straight into the framebuffer in the layout of
+ * the current mode, or with GraphicsOutput::blt if the mode has no framebuffer (BltOnly).
+ */
+pub struct UefiDisplay<'a, 'boot> {
+    gop: &'a mut GraphicsOutput<'boot>,
+    // The mode the fields below are laid out for
+    mode_info: ModeInfo,
+    // None for BltOnly
+    masks: Option<PixelMasks>,
+    stride: usize,
+    letterbox: Letterbox,
+    // The scaled scene for blt, only used without a framebuffer
+    blt_buffer: Vec<BltPixel>,
+    // One scaled row in the layout of the framebuffer
+    row_buffer: Vec<u32>,
+    // Whether the bars around the scene are black, not left over from the text console.
+    bars_cleared: bool,
+}
+
+impl<'a, 'boot> UefiDisplay<'a, 'boot> {
+    pub fn new(gop: &'a mut GraphicsOutput<'boot>, letterbox: Letterbox) -> Self {
+        let mode_info = gop.current_mode_info();
+        let masks = match mode_info.pixel_format() {
+            PixelFormat::Rgb => Some(PixelMasks::RGB),
+            PixelFormat::Bgr => Some(PixelMasks::BGR),
+            PixelFormat::Bitmask => mode_info.pixel_bitmask().map(|bitmask| PixelMasks {
+                red: bitmask.red,
+                green: bitmask.green,
+                blue: bitmask.blue,
+            }),
+            PixelFormat::BltOnly => None,
+        };
+        let stride = mode_info.stride();
+
+        let (width, height) = (VirtualFrameBuffer::WIDTH * letterbox.scale, VirtualFrameBuffer::HEIGHT * letterbox.scale);
+        let (blt_buffer, row_buffer) = match masks {
+            Some(_) => (vec![], vec![0; width]),
+            None => (vec![BltPixel::new(0, 0, 0); width * height], vec![]),
+        };
+
+        UefiDisplay {
+            gop,
+            mode_info,
+            masks,
+            stride,
+            letterbox,
+            blt_buffer,
+            row_buffer,
+            bars_cleared: false,
+        }
+    }
+
+    /*
+     * Text was printed over the scene, the next present clears the screen and shows all of it.
+     * Resetting the text console may have switched the GOP to the console's own mode, then the
+     * mode of the display is set again before anything is written to the framebuffer.
+     */
+    pub fn text_shown(&mut self) {
+        self.bars_cleared = false;
+
+        if !same_mode(&self.gop.current_mode_info(), &self.mode_info) {
+            let mode = self.gop.modes().find(|mode| same_mode(mode.info(), &self.mode_info)).expect("the graphics mode is gone");
+            self.gop.set_mode(&mode).expect("failed to set graphics mode");
+        }
+    }
+}
+
+impl<'a, 'boot> Display for UefiDisplay<'a, 'boot> {
+    // Only the dirty parts of the buffer are copied to the screen.
+    fn present(&mut self, vfb: &VirtualFrameBuffer) {
+        let full = vfb.rect();
+        // Camera moves and new masks only redraw the scene, the bars stay black.
+        let rects = if !self.bars_cleared {
+            self.clear_screen();
+            self.bars_cleared = true;
+            core::slice::from_ref(&full)
+        } else if vfb.dirty.contains(&full) {
+            core::slice::from_ref(&full)
+        } else {
+            &vfb.dirty[..]
+        };
+
+        for rect in rects {
+            match self.masks {
+                Some(masks) => draw_vfb_to_fb(&mut self.gop.frame_buffer(), self.stride, &masks, &self.letterbox, &mut self.row_buffer, vfb, rect),
+                None => self.blt_vfb(vfb, rect),
+            }
+        }
+    }
+}
+
+impl<'a, 'boot> UefiDisplay<'a, 'boot> {
+    fn clear_screen(&mut self) {
+        self.gop
+            .blt(BltOp::VideoFill {
+                color: BltPixel::new(0, 0, 0),
+                dest: (0, 0),
+                dims: (self.letterbox.screen_width, self.letterbox.screen_height),
+            })
+            .expect("failed to clear the screen");
+    }
+
+    fn blt_vfb(&mut self, vfb: &VirtualFrameBuffer, rect: &Rect) {
+        let scale = self.letterbox.scale;
+        let width = vfb.width() * scale;
+        let (rect_x, rect_y) = (rect.x as usize * scale, rect.y as usize * scale);
+        let (rect_width, rect_height) = (rect.width * scale, rect.height * scale);
+        for y in rect.y as usize..rect.y as usize + rect.height {
+            // The first of the scaled rows, then copies of it.
+            let start = y * scale * width + rect_x;
+            let source = &vfb.row(y)[rect.x as usize..rect.x as usize + rect.width];
+            for (i, pixel) in source.iter().enumerate() {
+                let blt_pixel = BltPixel::new((pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8);
+                self.blt_buffer[start + i * scale..start + (i + 1) * scale].fill(blt_pixel);
+            }
+            for block_y in 1..scale {
+                self.blt_buffer.copy_within(start..start + rect_width, start + block_y * width);
+            }
+        }
+
+        self.gop
+            .blt(BltOp::BufferToVideo {
+                buffer: &self.blt_buffer,
+                src: BltRegion::SubRectangle { coords: (rect_x, rect_y), px_stride: width },
+                dest: (self.letterbox.offset_x + rect_x, self.letterbox.offset_y + rect_y),
+                dims: (rect_width, rect_height),
+            })
+            .expect("failed to blt the frame");
+    }
+}
+
+// Whether the framebuffer of both modes has the same layout.
+fn same_mode(a: &ModeInfo, b: &ModeInfo) -> bool {
+    a.resolution() == b.resolution() && a.stride() == b.stride() && a.pixel_format() == b.pixel_format()
+}
+
+/*
+ * Every pixel of rect in the buffer becomes a letterbox.scale x letterbox.scale block. Rows
+ * are copied as a whole; as they are if the framebuffer has the layout of the buffer and
+ * isn't scaled, otherwise they are converted and scaled in row_buffer first.
+ */
+fn draw_vfb_to_fb(fb: &mut FrameBuffer, stride: usize, masks: &PixelMasks, letterbox: &Letterbox, row_buffer: &mut [u32], vfb: &VirtualFrameBuffer, rect: &Rect) {
+    let scale = letterbox.scale;
+    let fb_x = letterbox.offset_x + rect.x as usize * scale;
+    for y in rect.y as usize..rect.y as usize + rect.height {
+        let source = &vfb.row(y)[rect.x as usize..rect.x as usize + rect.width];
+        let line = if *masks == VirtualFrameBuffer::MASKS && scale == 1 {
+            source
+        } else {
+            for (i, pixel) in source.iter().enumerate() {
+                row_buffer[i * scale..(i + 1) * scale].fill(masks.repack(*pixel));
+            }
+            &row_buffer[..rect.width * scale]
+        };
+
+        for block_y in 0..scale {
+            let pixel_index = ((letterbox.offset_y + y * scale + block_y) * stride) + fb_x;
+            unsafe {
+                core::ptr::copy_nonoverlapping(line.as_ptr() as *const u8, fb.as_mut_ptr().add(4 * pixel_index), 4 * line.len());
+            }
+        }
+    }
+}